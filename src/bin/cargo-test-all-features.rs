@@ -1,6 +1,9 @@
 use cargo_all_features::run;
-use std::error::Error;
+use std::process;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    run()
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("Error: {err}");
+        process::exit(1);
+    }
 }
@@ -0,0 +1,282 @@
+//! The result cache under the target directory, which records the feature combinations that
+//! passed along with a hash of everything their outcome depends on, so that `--cache` can skip
+//! them for as long as none of it changes.
+use crate::{
+    cargo_metadata::{Metadata, Package},
+    json_file, report, Combination,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    env, error, fs, io, path, process,
+};
+
+/// The environment variables with flags for the compiler, which change the outcome as much as the
+/// toolchain, e.g. `RUSTFLAGS="-D warnings"`
+const FLAG_VARIABLES: &[&str] = &["RUSTFLAGS", "CARGO_ENCODED_RUSTFLAGS", "RUSTDOCFLAGS"];
+
+/// The combinations that passed, as recorded in the cache
+pub(crate) struct Cache {
+    path: path::PathBuf,
+    /// The keys of the combinations that passed
+    passed: BTreeSet<String>,
+    /// The hash of the sources, Cargo.lock, cargo configuration, toolchain, compiler flags, cargo
+    /// command and arguments of every package, by package id
+    fingerprints: HashMap<String, Vec<u8>>,
+}
+
+impl Cache {
+    /// Reads the cache in the target directory, if there is one, and hashes the inputs of running
+    /// `command` with `args` for each of the `packages`. A cache that can't be read is replaced
+    /// after a warning.
+    pub(crate) fn load<'a>(
+        metadata: &Metadata,
+        packages: impl Iterator<Item = &'a Package>,
+        command: &str,
+        args: &[String],
+    ) -> Result<Self, Box<dyn error::Error>> {
+        let path = metadata
+            .target_directory
+            .join("cargo-all-features")
+            .join("cache.json");
+        let passed = json_file::read(&path, |json| {
+            json["passed"]
+                .members()
+                .map(|key| key.as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_else(|err| {
+            // The cache only saves work, like the state
+            report::print_warning(&format!("{err}, starting from an empty cache"));
+            Default::default()
+        });
+
+        let toolchain = toolchain_version(&metadata.workspace_root)?;
+        let flags: Vec<_> = FLAG_VARIABLES
+            .iter()
+            .map(|&name| {
+                let value = env::var_os(name).map(|value| value.as_encoded_bytes().to_vec());
+                (name, value)
+            })
+            .collect();
+        let lockfile = read_if_exists(&metadata.workspace_root.join("Cargo.lock"))?;
+        // The workspace manifest can affect every member, e.g. with `[workspace.dependencies]`
+        // or `[profile]`
+        let workspace_manifest = read_if_exists(&metadata.workspace_root.join("Cargo.toml"))?;
+        let mut fingerprints = HashMap::new();
+        for package in packages {
+            if fingerprints.contains_key(&package.id) {
+                continue;
+            }
+            let mut hasher = Hasher::default();
+            hasher.update(toolchain.as_bytes());
+            for (name, value) in &flags {
+                hasher.update(name.as_bytes());
+                hasher.update_optional(value.as_deref());
+            }
+            hasher.update_optional(lockfile.as_deref());
+            hasher.update_optional(workspace_manifest.as_deref());
+            hasher.update(command.as_bytes());
+            hasher.update_all(args.iter().map(String::as_bytes));
+            for dir in source_dirs(metadata, package) {
+                hash_dir(&mut hasher, &dir, &dir, &metadata.target_directory)?;
+            }
+            hash_cargo_configs(&mut hasher, &package.manifest_path)?;
+            fingerprints.insert(package.id.clone(), hasher.finish());
+        }
+
+        Ok(Cache {
+            path,
+            passed,
+            fingerprints,
+        })
+    }
+
+    /// Whether `combination` passed before, with the same sources, Cargo.lock, cargo
+    /// configuration, toolchain, compiler flags, cargo command and arguments
+    pub(crate) fn passed(&self, combination: &Combination) -> bool {
+        self.passed.contains(&self.key(combination))
+    }
+
+    /// Records that `combination` passed
+    pub(crate) fn record_pass(&mut self, combination: &Combination) -> io::Result<()> {
+        self.passed.insert(self.key(combination));
+        self.save()
+    }
+
+    fn key(&self, combination: &Combination) -> String {
+        let mut hasher = Hasher::default();
+        hasher.update(&self.fingerprints[&combination.package.id]);
+        hasher.update_all(
+            combination
+                .feature_set
+                .iter()
+                .map(|feature| feature.as_bytes()),
+        );
+        hasher
+            .finish()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let passed: Vec<&str> = self.passed.iter().map(String::as_str).collect();
+        let json = json::object! { passed: passed };
+        json_file::write(&self.path, json)
+    }
+}
+
+/// The output of `rustc -vV`, run in the workspace so that a `rust-toolchain.toml` is respected
+fn toolchain_version(workspace_root: &path::Path) -> Result<String, Box<dyn error::Error>> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = process::Command::new(rustc)
+        .arg("-vV")
+        .current_dir(workspace_root)
+        .stderr(process::Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "Could not determine the toolchain version for the cache, `rustc -vV` failed with {}",
+            output.status
+        )
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn read_if_exists(path: &path::Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// The directories of `package` and of all its path dependencies, recursively
+fn source_dirs(metadata: &Metadata, package: &Package) -> BTreeSet<path::PathBuf> {
+    let package_dir = |package: &Package| {
+        package
+            .manifest_path
+            .parent()
+            .expect("could not find parent of cargo manifest path")
+            .to_owned()
+    };
+    let mut dirs = BTreeSet::new();
+    let mut packages = vec![package];
+    while let Some(package) = packages.pop() {
+        if !dirs.insert(package_dir(package)) {
+            continue;
+        }
+        for dependency_dir in package.dependencies.iter().filter_map(|d| d.path.as_ref()) {
+            match metadata
+                .packages
+                .iter()
+                .find(|package| package_dir(package) == *dependency_dir)
+            {
+                Some(dependency) => packages.push(dependency),
+                None => {
+                    dirs.insert(dependency_dir.clone());
+                }
+            }
+        }
+    }
+    dirs
+}
+
+/// Hashes the cargo configuration files that apply to running cargo next to `manifest_path`: the
+/// `.cargo/config.toml` and `.cargo/config` of its directory and of every ancestor, including the
+/// workspace root. They can set `rustflags`, `build.target` and the like.
+fn hash_cargo_configs(hasher: &mut Hasher, manifest_path: &path::Path) -> io::Result<()> {
+    for (depth, dir) in manifest_path.ancestors().skip(1).enumerate() {
+        for name in ["config.toml", "config"] {
+            // Only the files that exist, so that moving the checkout doesn't change the hash
+            if let Some(contents) = read_if_exists(&dir.join(".cargo").join(name))? {
+                hasher.update(&(depth as u64).to_le_bytes());
+                hasher.update(name.as_bytes());
+                hasher.update(&contents);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Hashes the names and contents of the files in `dir`, relative to `root` so that moving the
+/// checkout doesn't change the hash. The target directory, other build outputs and `.git` are
+/// skipped.
+fn hash_dir(
+    hasher: &mut Hasher,
+    root: &path::Path,
+    dir: &path::Path,
+    target_directory: &path::Path,
+) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        if path == target_directory || entry.file_name() == ".git" || is_build_output(&path) {
+            continue;
+        }
+        let relative_path = path.strip_prefix(root).unwrap_or(&path);
+        hasher.update(relative_path.to_string_lossy().as_bytes());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            hasher.update(b"dir");
+            hash_dir(hasher, root, &path, target_directory)?;
+            hasher.update(b"end of dir");
+        } else if file_type.is_symlink() {
+            hasher.update(b"symlink");
+            hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+        } else {
+            hasher.update(b"file");
+            hasher.update(&fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` is a target directory other than the one in use, e.g. one left over from
+/// building a workspace member on its own, which can be huge. Cargo tags its target directories
+/// with a `CACHEDIR.TAG`, but older versions didn't, so `target` next to a manifest counts too.
+fn is_build_output(path: &path::Path) -> bool {
+    let next_to_manifest = || {
+        path.parent()
+            .is_some_and(|dir| dir.join("Cargo.toml").is_file())
+    };
+    path.join("CACHEDIR.TAG").is_file()
+        || (path.file_name() == Some("target".as_ref()) && path.is_dir() && next_to_manifest())
+}
+
+/// SHA-256 of a sequence of byte strings, each prefixed with its length so that the sequence is
+/// unambiguous. Unlike the hashers of the standard library, its output is the same for every
+/// build of cargo-all-features, so the cache survives updating the toolchain that builds it.
+#[derive(Default)]
+struct Hasher(Sha256);
+
+impl Hasher {
+    fn update(&mut self, bytes: &[u8]) {
+        self.0.update((bytes.len() as u64).to_le_bytes());
+        self.0.update(bytes);
+    }
+
+    fn update_optional(&mut self, bytes: Option<&[u8]>) {
+        match bytes {
+            Some(bytes) => {
+                self.update(b"some");
+                self.update(bytes);
+            }
+            None => self.update(b"none"),
+        }
+    }
+
+    fn update_all<'a>(&mut self, items: impl ExactSizeIterator<Item = &'a [u8]>) {
+        self.0.update((items.len() as u64).to_le_bytes());
+        for bytes in items {
+            self.update(bytes);
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.0.finalize().to_vec()
+    }
+}
@@ -1,26 +1,366 @@
+use crate::constraints::{self, Expr};
 use crate::types::{Feature, FeatureList};
+use crate::validation;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
-use std::{error, path, process};
+use std::{error, fmt, io, path, process, string};
 
-pub fn fetch() -> Result<Metadata, Box<dyn error::Error>> {
-    let json = fetch_cargo_metadata_json()?;
-    let json = json::parse(&json)?;
-    Ok(Metadata::try_from(json)?)
+/// Runs `cargo metadata`, for the manifest at `manifest_path` if any. The options of the
+/// workspace members are those for the cargo `command` and the matrix `profile`, if any. The
+/// options in `overrides`, usually set on the command line, take precedence over those in the
+/// manifests of the workspace members, except for `skip_feature_sets`, which are added to those of
+/// the manifests.
+pub fn fetch(
+    manifest_path: Option<&path::Path>,
+    command: Option<&str>,
+    profile: Option<&str>,
+    overrides: &json::JsonValue,
+) -> Result<Metadata, Error> {
+    let json = fetch_cargo_metadata_json(manifest_path)?;
+    let json = json::parse(&json).map_err(Error::Json)?;
+    Metadata::parse(json, command, profile, overrides)
 }
 
-fn fetch_cargo_metadata_json() -> Result<String, Box<dyn error::Error>> {
+fn fetch_cargo_metadata_json(manifest_path: Option<&path::Path>) -> Result<String, Error> {
     let mut command = process::Command::new(crate::cargo_cmd());
 
     command.arg("metadata").arg("--format-version").arg("1");
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
 
-    let output = command.stderr(process::Stdio::inherit()).output()?;
+    let output = command
+        .stderr(process::Stdio::inherit())
+        .output()
+        .map_err(Error::Io)?;
 
     if !output.status.success() {
-        return Err("`cargo metadata` returned a non-zero status".into());
+        return Err(Error::Status(output.status));
     }
 
-    Ok(String::from_utf8(output.stdout)?)
+    String::from_utf8(output.stdout).map_err(Error::Utf8)
+}
+
+/// An error running `cargo metadata` or in its output, including the options of the packages.
+///
+/// `path` is the JSON path of the offending value, relative to the package it belongs to if there
+/// is a `package`, e.g. `dependencies[0].name` or `metadata.cargo-all-features.denylist`.
+/// Options without a package are those of the workspace.
+#[derive(Debug)]
+pub enum Error {
+    /// `cargo metadata` could not be run
+    Io(io::Error),
+    /// `cargo metadata` failed
+    Status(process::ExitStatus),
+    /// The output of `cargo metadata` is not UTF-8
+    Utf8(string::FromUtf8Error),
+    /// The output of `cargo metadata` is not JSON
+    Json(json::Error),
+    /// A value is missing
+    MissingField {
+        package: Option<String>,
+        path: String,
+    },
+    /// A value is of the wrong type
+    InvalidType {
+        package: Option<String>,
+        path: String,
+        expected: &'static str,
+    },
+    /// An option that doesn't exist, maybe a misspelling of `suggestion`
+    UnknownKey {
+        package: Option<String>,
+        path: String,
+        suggestion: Option<&'static str>,
+    },
+    /// An option names a feature that the package doesn't have
+    UnknownFeature {
+        package: String,
+        path: String,
+        feature: String,
+    },
+    /// `strategy` is not one of the known strategies
+    UnknownStrategy {
+        package: String,
+        path: String,
+        strategy: String,
+    },
+    /// An entry of `constraints` can't be parsed
+    InvalidConstraint {
+        package: String,
+        path: String,
+        constraint: String,
+        error: constraints::ParseError,
+    },
+    /// Options that contradict each other
+    ConflictingOptions {
+        package: String,
+        path: String,
+        conflict: Conflict,
+    },
+}
+
+/// How options contradict each other
+#[derive(Clone, Debug)]
+pub enum Conflict {
+    /// `allowlist` is combined with an option that it replaces
+    Allowlist { option: &'static str },
+    /// A feature is in `always_include_features` and in an `option` skipping it
+    AlwaysIncludedAndSkipped {
+        feature: String,
+        option: &'static str,
+    },
+    /// Several features of a `mutually_exclusive_features` group are in `always_include_features`
+    MutuallyExclusive { features: Vec<String> },
+    /// Several features of a `require_one_of` group are in `always_include_features`
+    RequireOneOf { features: Vec<String> },
+}
+
+impl Error {
+    /// The table and the key of an error in the options
+    fn table_and_key<'a>(package: &Option<String>, path: &'a str) -> (String, &'a str) {
+        let (table, key) = path.rsplit_once('.').unwrap_or(("", path));
+        let root = if package.is_some() {
+            "package"
+        } else {
+            "workspace"
+        };
+        (format!("{root}.{table}"), key)
+    }
+}
+
+/// Formats the name of the package of an error in the options
+struct Subject<'a>(&'a Option<String>);
+
+impl fmt::Display for Subject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(package) => write!(f, "Package {package}"),
+            None => f.write_str("Workspace"),
+        }
+    }
+}
+
+/// Formats the name of the package of an error in the output of `cargo metadata`
+struct Output<'a>(&'a Option<String>);
+
+impl fmt::Display for Output<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(package) => write!(f, "Package {package} in the output of `cargo metadata`"),
+            None => f.write_str("The output of `cargo metadata`"),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "Could not run `cargo metadata`: {err}"),
+            Error::Status(_) => f.write_str("`cargo metadata` returned a non-zero status"),
+            Error::Utf8(err) => write!(f, "The output of `cargo metadata` is not UTF-8: {err}"),
+            Error::Json(err) => write!(f, "The output of `cargo metadata` is not JSON: {err}"),
+            Error::MissingField { package, path } => {
+                write!(f, "{} has no `{path}`", Output(package))
+            }
+            Error::InvalidType {
+                package,
+                path,
+                expected,
+            } if path.starts_with("metadata.cargo-all-features") => {
+                let (table, key) = Error::table_and_key(package, path);
+                write!(
+                    f,
+                    "{} has `{key}` of the wrong type in `[{table}]`, expected {expected}",
+                    Subject(package)
+                )
+            }
+            Error::InvalidType {
+                package,
+                path,
+                expected,
+            } => write!(
+                f,
+                "{} has `{path}` of the wrong type, expected {expected}",
+                Output(package)
+            ),
+            Error::UnknownKey {
+                package,
+                path,
+                suggestion,
+            } => {
+                let (table, key) = Error::table_and_key(package, path);
+                write!(
+                    f,
+                    "{} has unknown key `{key}` in `[{table}]`",
+                    Subject(package)
+                )?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean `{suggestion}`?"),
+                    None => Ok(()),
+                }
+            }
+            Error::UnknownFeature {
+                package,
+                path,
+                feature,
+            } => {
+                let (table, key) = Error::table_and_key(&Some(package.clone()), path);
+                write!(
+                    f,
+                    "Package {package} has unknown feature `{feature}` in `{key}` of `[{table}]`"
+                )
+            }
+            Error::UnknownStrategy {
+                package, strategy, ..
+            } => write!(
+                f,
+                "Package {package} has unknown `strategy` {strategy:?}, expected \"powerset\", \"pairwise\" or \"<t>-wise\""
+            ),
+            Error::InvalidConstraint {
+                package,
+                constraint,
+                error,
+                ..
+            } => write!(
+                f,
+                "Package {package} has invalid constraint {constraint:?}: {error}"
+            ),
+            Error::ConflictingOptions {
+                package, conflict, ..
+            } => match conflict {
+                Conflict::Allowlist { option } => write!(
+                    f,
+                    "Package {package} has both `allowlist` and `{option}` keys"
+                ),
+                Conflict::AlwaysIncludedAndSkipped { feature, option } => write!(
+                    f,
+                    "Package {package} has feature {feature} in both `{option}` and `always_include_features`"
+                ),
+                Conflict::MutuallyExclusive { features } => write!(
+                    f,
+                    "Package {package} has mutually exclusive features {} in `always_include_features`",
+                    features.join(" and ")
+                ),
+                Conflict::RequireOneOf { features } => write!(
+                    f,
+                    "Package {package} has features {} of the same `require_one_of` group in `always_include_features`",
+                    features.join(" and ")
+                ),
+            },
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Utf8(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::InvalidConstraint { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// The string at `path`, of the `package` if any
+fn as_str<'a>(
+    value: &'a json::JsonValue,
+    package: Option<&str>,
+    path: &str,
+) -> Result<&'a str, Error> {
+    optional_str(value, package, path)?.ok_or_else(|| Error::MissingField {
+        package: package.map(str::to_owned),
+        path: path.to_owned(),
+    })
+}
+
+/// The string at `path`, of the `package` if any, or `None` if there is no value
+fn optional_str<'a>(
+    value: &'a json::JsonValue,
+    package: Option<&str>,
+    path: &str,
+) -> Result<Option<&'a str>, Error> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    value.as_str().map(Some).ok_or_else(|| Error::InvalidType {
+        package: package.map(str::to_owned),
+        path: path.to_owned(),
+        expected: "a string",
+    })
+}
+
+/// The boolean at `path`, of the `package` if any, or `None` if there is no value
+fn optional_bool(
+    value: &json::JsonValue,
+    package: Option<&str>,
+    path: &str,
+) -> Result<Option<bool>, Error> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    value.as_bool().map(Some).ok_or_else(|| Error::InvalidType {
+        package: package.map(str::to_owned),
+        path: path.to_owned(),
+        expected: "a boolean",
+    })
+}
+
+/// The array of strings at `path`, of the `package` if any
+fn strings<'a>(
+    value: &'a json::JsonValue,
+    package: Option<&str>,
+    path: &str,
+) -> Result<Vec<&'a str>, Error> {
+    if !value.is_null() && !value.is_array() {
+        return Err(Error::InvalidType {
+            package: package.map(str::to_owned),
+            path: path.to_owned(),
+            expected: "an array of strings",
+        });
+    }
+    value
+        .members()
+        .enumerate()
+        .map(|(i, member)| as_str(member, package, &format!("{path}[{i}]")))
+        .collect()
+}
+
+/// The option `key` of a `package`, a list of features
+fn feature_list(config: &json::JsonValue, package: &str, key: &str) -> Result<FeatureList, Error> {
+    let path = format!("metadata.cargo-all-features.{key}");
+    Ok(strings(&config[key], Some(package), &path)?
+        .into_iter()
+        .map(|feature| Feature(feature.to_owned()))
+        .collect())
+}
+
+/// The option `key` of a `package`, a list of lists of features
+fn feature_sets(
+    config: &json::JsonValue,
+    package: &str,
+    key: &str,
+) -> Result<Vec<FeatureList>, Error> {
+    let path = format!("metadata.cargo-all-features.{key}");
+    if !config[key].is_null() && !config[key].is_array() {
+        return Err(Error::InvalidType {
+            package: Some(package.to_owned()),
+            path,
+            expected: "an array of arrays of strings",
+        });
+    }
+    config[key]
+        .members()
+        .enumerate()
+        .map(|(i, set)| {
+            Ok(strings(set, Some(package), &format!("{path}[{i}]"))?
+                .into_iter()
+                .map(|feature| Feature(feature.to_owned()))
+                .collect())
+        })
+        .collect()
 }
 
 #[derive(Clone, Debug)]
@@ -28,19 +368,32 @@ pub struct Dependency {
     pub name: String,
     pub rename: Option<String>,
     pub optional: bool,
+    /// The directory of a path dependency
+    pub path: Option<path::PathBuf>,
 }
 
-impl From<json::JsonValue> for Dependency {
-    fn from(json_value: json::JsonValue) -> Self {
-        let name = json_value["name"].as_str().unwrap().to_owned();
-        let rename = json_value["rename"].as_str().map(|s| s.to_string());
-        let optional = json_value["optional"].as_bool().unwrap();
+impl Dependency {
+    /// Parses the dependency at `path` of the `package`
+    fn parse(json_value: &json::JsonValue, package: &str, path: &str) -> Result<Self, Error> {
+        let package = Some(package);
+        let name = as_str(&json_value["name"], package, &format!("{path}.name"))?.to_owned();
+        let rename = optional_str(&json_value["rename"], package, &format!("{path}.rename"))?
+            .map(|s| s.to_string());
+        let optional_path = format!("{path}.optional");
+        let optional = optional_bool(&json_value["optional"], package, &optional_path)?
+            .ok_or_else(|| Error::MissingField {
+                package: package.map(str::to_owned),
+                path: optional_path,
+            })?;
+        let path = optional_str(&json_value["path"], package, &format!("{path}.path"))?
+            .map(path::PathBuf::from);
 
-        Dependency {
+        Ok(Dependency {
             name,
             rename,
             optional,
-        }
+            path,
+        })
     }
 }
 
@@ -54,25 +407,70 @@ pub struct Package {
     pub feature_map: HashMap<String, FeatureList>,
     pub skip_package: bool,
     pub skip_feature_sets: Vec<FeatureList>,
+    pub mutually_exclusive_features: Vec<FeatureList>,
+    pub require_one_of: Vec<FeatureList>,
+    pub feature_requires: HashMap<Feature, FeatureList>,
+    pub constraints: Vec<Expr>,
     pub skip_optional_dependencies: bool,
     pub allowlist: FeatureList,
     pub denylist: HashSet<Feature>,
     pub extra_features: FeatureList,
     pub always_include_features: FeatureList,
     pub max_combination_size: Option<usize>,
+    pub strategy: Strategy,
+}
+
+/// How feature sets are selected from the features of a package
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Strategy {
+    /// Every combination of features
+    Powerset,
+    /// A covering array in which every combination of enabling and disabling any `t` features
+    /// appears at least once
+    TWise(usize),
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Powerset => f.write_str("powerset"),
+            Strategy::TWise(2) => f.write_str("pairwise"),
+            Strategy::TWise(t) => write!(f, "{t}-wise"),
+        }
+    }
+}
+
+impl Strategy {
+    fn parse(strategy: &str) -> Option<Self> {
+        match strategy {
+            "powerset" => Some(Strategy::Powerset),
+            "pairwise" => Some(Strategy::TWise(2)),
+            _ => strategy
+                .strip_suffix("-wise")?
+                .parse()
+                .ok()
+                .filter(|&t| t > 0)
+                .map(Strategy::TWise),
+        }
+    }
 }
 
 impl TryFrom<json::JsonValue> for Package {
-    type Error = String;
-    fn try_from(json_value: json::JsonValue) -> Result<Self, String> {
-        let id = json_value["id"].as_str().unwrap().to_owned();
-        let name = json_value["name"].as_str().unwrap().to_owned();
-        let manifest_path =
-            path::PathBuf::from(json_value["manifest_path"].as_str().unwrap().to_owned());
+    type Error = Error;
+    fn try_from(json_value: json::JsonValue) -> Result<Self, Error> {
+        let name = as_str(&json_value["name"], None, "name")?.to_owned();
+        let package = Some(name.as_str());
+        let id = as_str(&json_value["id"], package, "id")?.to_owned();
+        let manifest_path = path::PathBuf::from(as_str(
+            &json_value["manifest_path"],
+            package,
+            "manifest_path",
+        )?);
         let dependencies = json_value["dependencies"]
             .members()
-            .map(|member| Dependency::from(member.to_owned()))
-            .collect();
+            .enumerate()
+            .map(|(i, member)| Dependency::parse(member, &name, &format!("dependencies[{i}]")))
+            .collect::<Result<_, _>>()?;
         let features = json_value["features"]
             .entries()
             .map(|(k, _v)| k.to_owned())
@@ -81,102 +479,161 @@ impl TryFrom<json::JsonValue> for Package {
         let feature_map = json_value["features"]
             .entries()
             .map(|(k, v)| {
-                (
+                let features = strings(v, package, &format!("features.{k}"))?;
+                Ok((
                     k.to_owned(),
-                    FeatureList(v.members().map(|v| Feature(v.to_string())).collect()),
-                )
+                    features
+                        .into_iter()
+                        .map(|v| Feature(v.to_owned()))
+                        .collect(),
+                ))
             })
-            .collect();
-        let skip_feature_sets: Vec<FeatureList> = json_value["metadata"]["cargo-all-features"]
-            ["skip_feature_sets"]
-            .members()
-            .map(|member| {
-                member
-                    .members()
-                    .map(|feature| feature.as_str().unwrap().to_owned())
-                    .map(Feature)
-                    .collect()
+            .collect::<Result<_, Error>>()?;
+
+        let config = &json_value["metadata"]["cargo-all-features"];
+        let skip_feature_sets = feature_sets(config, &name, "skip_feature_sets")?;
+        let mutually_exclusive_features =
+            feature_sets(config, &name, "mutually_exclusive_features")?;
+        let require_one_of = feature_sets(config, &name, "require_one_of")?;
+        let feature_requires: HashMap<Feature, FeatureList> = config["feature_requires"]
+            .entries()
+            .map(|(feature, requirements)| {
+                let path = format!("metadata.cargo-all-features.feature_requires.{feature}");
+                let requirements = strings(requirements, package, &path)?;
+                Ok((
+                    Feature(feature.to_owned()),
+                    requirements
+                        .into_iter()
+                        .map(|requirement| Feature(requirement.to_owned()))
+                        .collect(),
+                ))
             })
-            .collect();
-        let maybe_skip_optional =
-            json_value["metadata"]["cargo-all-features"]["skip_optional_dependencies"].as_bool();
+            .collect::<Result<_, Error>>()?;
+        let constraints = strings(
+            &config["constraints"],
+            package,
+            "metadata.cargo-all-features.constraints",
+        )?
+        .into_iter()
+        .enumerate()
+        .map(|(i, constraint)| {
+            Expr::parse(constraint).map_err(|error| Error::InvalidConstraint {
+                package: name.clone(),
+                path: format!("metadata.cargo-all-features.constraints[{i}]"),
+                constraint: constraint.to_owned(),
+                error,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+        let maybe_skip_optional = optional_bool(
+            &config["skip_optional_dependencies"],
+            package,
+            "metadata.cargo-all-features.skip_optional_dependencies",
+        )?;
         let skip_optional_dependencies: bool = maybe_skip_optional.unwrap_or(false);
-        let extra_features: FeatureList = json_value["metadata"]["cargo-all-features"]
-            ["extra_features"]
-            .members()
-            .map(|member| member.as_str().unwrap().to_owned())
-            .map(Feature)
-            .collect();
+        let extra_features = feature_list(config, &name, "extra_features")?;
 
-        let allowlist: FeatureList = json_value["metadata"]["cargo-all-features"]["allowlist"]
-            .members()
-            .map(|member| member.as_str().unwrap().to_owned())
-            .map(Feature)
-            .collect();
+        let allowlist = feature_list(config, &name, "allowlist")?;
 
-        let denylist: HashSet<_> = json_value["metadata"]["cargo-all-features"]["denylist"]
-            .members()
-            .map(|member| member.as_str().unwrap().to_owned())
-            .map(Feature)
-            .collect();
-        let always_include_features: FeatureList = json_value["metadata"]["cargo-all-features"]
-            ["always_include_features"]
-            .members()
-            .map(|member| member.as_str().unwrap().to_owned())
-            .map(Feature)
+        let denylist: HashSet<_> = feature_list(config, &name, "denylist")?
+            .0
+            .into_iter()
             .collect();
-        let max_combination_size =
-            json_value["metadata"]["cargo-all-features"]["max_combination_size"].as_usize();
+        let always_include_features = feature_list(config, &name, "always_include_features")?;
+        let max_combination_size = match &config["max_combination_size"] {
+            value if value.is_null() => None,
+            value => Some(value.as_usize().ok_or_else(|| Error::InvalidType {
+                package: Some(name.clone()),
+                path: "metadata.cargo-all-features.max_combination_size".to_owned(),
+                expected: "a non-negative integer",
+            })?),
+        };
 
-        let skip_package = json_value["metadata"]["cargo-all-features"]["skip_package"]
-            .as_bool()
-            .unwrap_or(false);
+        let strategy_path = "metadata.cargo-all-features.strategy";
+        let strategy = match optional_str(&config["strategy"], package, strategy_path)? {
+            None => Strategy::Powerset,
+            Some(strategy) => Strategy::parse(strategy).ok_or_else(|| Error::UnknownStrategy {
+                package: name.clone(),
+                path: strategy_path.to_owned(),
+                strategy: strategy.to_owned(),
+            })?,
+        };
+
+        let skip_package = optional_bool(
+            &config["skip_package"],
+            package,
+            "metadata.cargo-all-features.skip_package",
+        )?
+        .unwrap_or(false);
+
+        let conflict = |option: &str, conflict: Conflict| Error::ConflictingOptions {
+            package: name.clone(),
+            path: format!("metadata.cargo-all-features.{option}"),
+            conflict,
+        };
 
         if !allowlist.is_empty() {
-            if !always_include_features.is_empty() {
-                return Err(format!(
-                    "Package {name} has both `allowlist` and `always_include_features` keys"
-                ));
-            }
-            if !denylist.is_empty() {
-                return Err(format!(
-                    "Package {name} has both `allowlist` and `denylist` keys"
-                ));
-            }
-            if !extra_features.is_empty() {
-                return Err(format!(
-                    "Package {name} has both `allowlist` and `extra_features` keys"
-                ));
-            }
-            if maybe_skip_optional.is_some() {
-                return Err(format!(
-                    "Package {name} has both `allowlist` and `skip_optional_dependencies` keys"
-                ));
-            }
-            if max_combination_size.is_some() {
-                return Err(format!(
-                    "Package {name} has both `allowlist` and `max_combination_size` keys"
-                ));
+            let conflicting_option = [
+                (
+                    "always_include_features",
+                    !always_include_features.is_empty(),
+                ),
+                ("denylist", !denylist.is_empty()),
+                ("extra_features", !extra_features.is_empty()),
+                ("skip_optional_dependencies", maybe_skip_optional.is_some()),
+            ]
+            .into_iter()
+            .find(|&(_, is_set)| is_set);
+            if let Some((option, _)) = conflicting_option {
+                return Err(conflict("allowlist", Conflict::Allowlist { option }));
             }
         }
 
         if !always_include_features.is_empty() {
             let always: HashSet<_> = always_include_features.iter().collect();
+            let always_included_and_skipped = |feature: &Feature, option| {
+                conflict(
+                    "always_include_features",
+                    Conflict::AlwaysIncludedAndSkipped {
+                        feature: feature.0.clone(),
+                        option,
+                    },
+                )
+            };
             for set in &skip_feature_sets {
                 for feature in set.iter() {
                     if always.contains(&feature) {
-                        return Err(format!(
-                            "Package {} has feature {} in both `skip_feature_sets` and `always_include_features`",
-                            name, &**feature
-                        ));
+                        return Err(always_included_and_skipped(feature, "skip_feature_sets"));
                     }
                 }
             }
             for feature in denylist.iter() {
                 if always.contains(&feature) {
-                    return Err(format!(
-                        "Package {} has feature {} in both `denylist` and `always_include_features`",
-                        name, &**feature
+                    return Err(always_included_and_skipped(feature, "denylist"));
+                }
+            }
+            let always_included = |group: &FeatureList| -> Vec<String> {
+                group
+                    .iter()
+                    .filter(|feature| always.contains(feature))
+                    .map(|feature| feature.0.clone())
+                    .collect()
+            };
+            for group in &mutually_exclusive_features {
+                let features = always_included(group);
+                if features.len() > 1 {
+                    return Err(conflict(
+                        "mutually_exclusive_features",
+                        Conflict::MutuallyExclusive { features },
+                    ));
+                }
+            }
+            for group in &require_one_of {
+                let features = always_included(group);
+                if features.len() > 1 {
+                    return Err(conflict(
+                        "require_one_of",
+                        Conflict::RequireOneOf { features },
                     ));
                 }
             }
@@ -191,43 +648,259 @@ impl TryFrom<json::JsonValue> for Package {
             feature_map,
             skip_package,
             skip_feature_sets,
+            mutually_exclusive_features,
+            require_one_of,
+            feature_requires,
+            constraints,
             skip_optional_dependencies,
             extra_features,
             allowlist,
             denylist,
             always_include_features,
             max_combination_size,
+            strategy,
         })
     }
 }
 
-#[derive(Clone)]
+impl Package {
+    /// The effective `cargo-all-features` configuration of the package, after merging in the
+    /// workspace defaults
+    pub fn config_to_json(&self) -> json::JsonValue {
+        fn features(features: &FeatureList) -> json::JsonValue {
+            features
+                .iter()
+                .map(|f| f.0.as_str())
+                .collect::<Vec<_>>()
+                .into()
+        }
+        fn groups(groups: &[FeatureList]) -> json::JsonValue {
+            groups.iter().map(features).collect::<Vec<_>>().into()
+        }
+
+        let mut denylist: Vec<&str> = self.denylist.iter().map(|f| f.0.as_str()).collect();
+        denylist.sort();
+        let mut feature_requires = json::JsonValue::new_object();
+        for (feature, requirements) in &self.feature_requires {
+            feature_requires[feature.0.as_str()] = features(requirements);
+        }
+
+        json::object! {
+            skip_package: self.skip_package,
+            skip_feature_sets: groups(&self.skip_feature_sets),
+            mutually_exclusive_features: groups(&self.mutually_exclusive_features),
+            require_one_of: groups(&self.require_one_of),
+            feature_requires: feature_requires,
+            constraints: self
+                .constraints
+                .iter()
+                .map(|constraint| constraint.to_string())
+                .collect::<Vec<_>>(),
+            skip_optional_dependencies: self.skip_optional_dependencies,
+            extra_features: features(&self.extra_features),
+            denylist: denylist,
+            always_include_features: features(&self.always_include_features),
+            max_combination_size: self.max_combination_size,
+            allowlist: features(&self.allowlist),
+            strategy: self.strategy.to_string(),
+        }
+    }
+}
+
+/// Keys that cannot be combined with `allowlist`
+const ALLOWLIST_CONFLICTS: &[&str] = &[
+    "always_include_features",
+    "denylist",
+    "extra_features",
+    "skip_optional_dependencies",
+];
+
+/// Overlays the keys of `config` over the inherited `defaults`, e.g. those of a package over the
+/// workspace-wide ones, those of a cargo command sub-table or a profile over the base options, or
+/// those of the command line.
+///
+/// A `config` setting `allowlist` does not inherit the keys that conflict with it, and a `config`
+/// setting any of those keys does not inherit `allowlist`.
+fn merge_config(defaults: &json::JsonValue, config: &json::JsonValue) -> json::JsonValue {
+    let mut merged = defaults.clone();
+    if config.has_key("allowlist") {
+        for key in ALLOWLIST_CONFLICTS {
+            merged.remove(key);
+        }
+    }
+    if ALLOWLIST_CONFLICTS.iter().any(|key| config.has_key(key)) {
+        merged.remove("allowlist");
+    }
+    for (key, value) in config.entries() {
+        merged[key] = value.clone();
+    }
+    merged
+}
+
+/// The options of `config` for the cargo `command` and the matrix `profile`: the base options,
+/// overridden by those of the `command` sub-table, e.g. `[package.metadata.cargo-all-features.test]`
+/// for `cargo test`, overridden by those of the profile, e.g.
+/// `[package.metadata.cargo-all-features.profiles.quick]` for `--profile quick`
+fn select_config(
+    config: &json::JsonValue,
+    command: Option<&str>,
+    profile: Option<&str>,
+) -> json::JsonValue {
+    let mut selected = config.clone();
+    if let Some(command) = command {
+        if config[command].is_object() {
+            selected = merge_config(&selected, &config[command]);
+        }
+    }
+    if let Some(profile) = profile {
+        if config["profiles"][profile].is_object() {
+            selected = merge_config(&selected, &config["profiles"][profile]);
+        }
+    }
+    selected
+}
+
+/// Applies `overrides` to the merged `config` of a package, with the same rule for `allowlist` as
+/// [`merge_config`]. `skip_feature_sets` are added instead of replaced.
+fn override_config(config: &json::JsonValue, overrides: &json::JsonValue) -> json::JsonValue {
+    let mut merged = merge_config(config, overrides);
+    if overrides.has_key("skip_feature_sets") {
+        merged["skip_feature_sets"] = config["skip_feature_sets"]
+            .members()
+            .chain(overrides["skip_feature_sets"].members())
+            .cloned()
+            .collect::<Vec<_>>()
+            .into();
+    }
+    merged
+}
+
+/// Whether `feature` names a feature or an optional dependency of `package`, or a feature of one of
+/// its dependencies with the `dependency/feature` syntax
+fn is_known_feature(package: &json::JsonValue, feature: &str) -> bool {
+    // The names of the dependencies, and whether they are optional
+    let mut dependencies = package["dependencies"].members().map(|dependency| {
+        let name = dependency["rename"]
+            .as_str()
+            .or(dependency["name"].as_str());
+        (name, dependency["optional"].as_bool() == Some(true))
+    });
+    let feature = feature.strip_prefix("dep:").unwrap_or(feature);
+    match feature.split_once('/') {
+        Some((dependency, _)) => {
+            let dependency = dependency.trim_end_matches('?');
+            dependencies.any(|(name, _)| name == Some(dependency))
+        }
+        None => {
+            package["features"].has_key(feature)
+                || dependencies.any(|(name, optional)| optional && name == Some(feature))
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Metadata {
     pub workspace_root: path::PathBuf,
+    pub target_directory: path::PathBuf,
     pub workspace_members: Vec<String>,
     pub packages: Vec<Package>,
+    /// The names of the matrix profiles defined by the workspace or its members
+    pub profiles: HashSet<String>,
 }
 
 impl TryFrom<json::JsonValue> for Metadata {
-    type Error = String;
-    fn try_from(json_value: json::JsonValue) -> Result<Self, String> {
-        let workspace_root =
-            path::PathBuf::from(json_value["workspace_root"].as_str().unwrap().to_owned());
+    type Error = Error;
+    fn try_from(json_value: json::JsonValue) -> Result<Self, Error> {
+        Metadata::parse(json_value, None, None, &json::JsonValue::new_object())
+    }
+}
 
-        let workspace_members = json_value["workspace_members"]
+impl Metadata {
+    fn parse(
+        json_value: json::JsonValue,
+        command: Option<&str>,
+        profile: Option<&str>,
+        overrides: &json::JsonValue,
+    ) -> Result<Self, Error> {
+        let workspace_root = path::PathBuf::from(as_str(
+            &json_value["workspace_root"],
+            None,
+            "workspace_root",
+        )?);
+        let target_directory = path::PathBuf::from(as_str(
+            &json_value["target_directory"],
+            None,
+            "target_directory",
+        )?);
+
+        let workspace_members: Vec<String> =
+            strings(&json_value["workspace_members"], None, "workspace_members")?
+                .into_iter()
+                .map(str::to_owned)
+                .collect();
+
+        let is_workspace_member = |package: &json::JsonValue| {
+            package["id"]
+                .as_str()
+                .is_some_and(|id| workspace_members.iter().any(|member| member == id))
+        };
+
+        // `[workspace.metadata.cargo-all-features]` holds defaults for the workspace members
+        let workspace_config = &json_value["metadata"]["cargo-all-features"];
+        validation::validate(
+            None,
+            "metadata.cargo-all-features",
+            workspace_config,
+            command,
+        )?;
+
+        let profiles = json_value["packages"]
             .members()
-            .map(|member| member.as_str().unwrap().to_owned())
+            .filter(|package| is_workspace_member(package))
+            .map(|package| &package["metadata"]["cargo-all-features"])
+            .chain([workspace_config])
+            .flat_map(|config| config["profiles"].entries())
+            .map(|(name, _)| name.to_owned())
             .collect();
 
+        let workspace_config = select_config(workspace_config, command, profile);
+
         let packages = json_value["packages"]
             .members()
-            .map(|member| Package::try_from(member.to_owned()))
-            .collect::<Result<_, String>>()?;
+            .enumerate()
+            .map(|(i, member)| {
+                let mut member = member.to_owned();
+                if is_workspace_member(&member) {
+                    let name =
+                        as_str(&member["name"], None, &format!("packages[{i}].name"))?.to_owned();
+                    let path = "metadata.cargo-all-features";
+                    let config = &member["metadata"]["cargo-all-features"];
+                    validation::validate(Some(&name), path, config, command)?;
+                    // The options of the workspace and of the command line apply to every member,
+                    // so only those of the package itself have to name its features
+                    validation::validate_feature_names(&name, path, config, &|feature| {
+                        is_known_feature(&member, feature)
+                    })?;
+                    let mut config =
+                        select_config(&member["metadata"]["cargo-all-features"], command, profile);
+                    if workspace_config.is_object() {
+                        config = merge_config(&workspace_config, &config);
+                    }
+                    member["metadata"]["cargo-all-features"] = override_config(&config, overrides);
+                } else {
+                    // Only workspace members are tested, don't trip over the options of others
+                    member["metadata"].remove("cargo-all-features");
+                }
+                Package::try_from(member)
+            })
+            .collect::<Result<_, Error>>()?;
 
         Ok(Metadata {
             workspace_root,
+            target_directory,
             workspace_members,
             packages,
+            profiles,
         })
     }
 }
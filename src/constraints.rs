@@ -0,0 +1,276 @@
+//! Boolean expressions over features, used by the `constraints` option to skip feature sets.
+//!
+//! From the loosest to the tightest binding, the operators are `<->` (if and only if), `->`
+//! (implies, right associative), `||`, `&&` and `!`. Parentheses group. Any other word is a
+//! feature name, which is true if the feature is enabled.
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Expr {
+    Feature(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Implies(Box<Expr>, Box<Expr>),
+    Iff(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let mut parser = Parser {
+            tokens: Lexer::new(source).collect::<Result<Vec<_>, _>>()?,
+            position: 0,
+            end: source.len(),
+        };
+        let expr = parser.iff()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(&(offset, ref token)) => Err(ParseError {
+                offset,
+                message: format!("expected an operator, found {token}"),
+            }),
+        }
+    }
+
+    /// Evaluates the expression, with `enabled` telling whether a feature is enabled
+    pub fn eval(&self, enabled: &impl Fn(&str) -> bool) -> bool {
+        match self {
+            Expr::Feature(feature) => enabled(feature),
+            Expr::Not(expr) => !expr.eval(enabled),
+            Expr::And(lhs, rhs) => lhs.eval(enabled) && rhs.eval(enabled),
+            Expr::Or(lhs, rhs) => lhs.eval(enabled) || rhs.eval(enabled),
+            Expr::Implies(lhs, rhs) => !lhs.eval(enabled) || rhs.eval(enabled),
+            Expr::Iff(lhs, rhs) => lhs.eval(enabled) == rhs.eval(enabled),
+        }
+    }
+
+    /// Every feature name in the expression
+    pub fn features(&self) -> Vec<&str> {
+        match self {
+            Expr::Feature(feature) => vec![feature],
+            Expr::Not(expr) => expr.features(),
+            Expr::And(lhs, rhs)
+            | Expr::Or(lhs, rhs)
+            | Expr::Implies(lhs, rhs)
+            | Expr::Iff(lhs, rhs) => {
+                let mut features = lhs.features();
+                features.extend(rhs.features());
+                features
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Feature(feature) => f.write_str(feature),
+            Expr::Not(expr) => write!(f, "!{expr}"),
+            Expr::And(lhs, rhs) => write!(f, "({lhs} && {rhs})"),
+            Expr::Or(lhs, rhs) => write!(f, "({lhs} || {rhs})"),
+            Expr::Implies(lhs, rhs) => write!(f, "({lhs} -> {rhs})"),
+            Expr::Iff(lhs, rhs) => write!(f, "({lhs} <-> {rhs})"),
+        }
+    }
+}
+
+/// An error in a constraint, at a byte offset of its source
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Token {
+    Feature(String),
+    Not,
+    And,
+    Or,
+    Implies,
+    Iff,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Feature(feature) => write!(f, "feature `{feature}`"),
+            Token::Not => f.write_str("`!`"),
+            Token::And => f.write_str("`&&`"),
+            Token::Or => f.write_str("`||`"),
+            Token::Implies => f.write_str("`->`"),
+            Token::Iff => f.write_str("`<->`"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+        }
+    }
+}
+
+struct Lexer<'a> {
+    source: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Lexer {
+            source,
+            chars: source.char_indices().peekable(),
+        }
+    }
+
+    /// Consumes `expected` after the first character of an operator at `offset`
+    fn expect(&mut self, offset: usize, expected: &str, token: Token) -> Result<Token, ParseError> {
+        for expected in expected.chars() {
+            if self.chars.next_if(|&(_, c)| c == expected).is_none() {
+                return Err(ParseError {
+                    offset,
+                    message: "unknown operator".to_owned(),
+                });
+            }
+        }
+        Ok(token)
+    }
+}
+
+fn is_feature_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '+' | '.' | '/' | ':' | '?')
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<(usize, Token), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let (offset, c) = self.chars.next()?;
+        let token = match c {
+            '!' => Ok(Token::Not),
+            '(' => Ok(Token::Open),
+            ')' => Ok(Token::Close),
+            '&' => self.expect(offset, "&", Token::And),
+            '|' => self.expect(offset, "|", Token::Or),
+            '<' => self.expect(offset, "->", Token::Iff),
+            '-' if self.chars.peek().map(|&(_, c)| c) == Some('>') => {
+                self.expect(offset, ">", Token::Implies)
+            }
+            c if is_feature_char(c) => {
+                let mut end = offset + c.len_utf8();
+                while let Some(&(i, c)) = self.chars.peek() {
+                    // A feature name may contain `-`, but not `->`
+                    let implies = c == '-' && self.source[i..].starts_with("->");
+                    if !is_feature_char(c) || implies {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    self.chars.next();
+                }
+                Ok(Token::Feature(self.source[offset..end].to_owned()))
+            }
+            c => Err(ParseError {
+                offset,
+                message: format!("unexpected character `{c}`"),
+            }),
+        };
+        Some(token.map(|token| (offset, token)))
+    }
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Offset of the end of the source, for errors about a missing token
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(usize, Token)> {
+        self.tokens.get(self.position)
+    }
+
+    fn next_if(&mut self, token: &Token) -> bool {
+        let matches = self.peek().is_some_and(|(_, next)| next == token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn iff(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.implies()?;
+        while self.next_if(&Token::Iff) {
+            lhs = Expr::Iff(Box::new(lhs), Box::new(self.implies()?));
+        }
+        Ok(lhs)
+    }
+
+    fn implies(&mut self) -> Result<Expr, ParseError> {
+        let lhs = self.or()?;
+        if self.next_if(&Token::Implies) {
+            Ok(Expr::Implies(Box::new(lhs), Box::new(self.implies()?)))
+        } else {
+            Ok(lhs)
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.and()?;
+        while self.next_if(&Token::Or) {
+            lhs = Expr::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut lhs = self.not()?;
+        while self.next_if(&Token::And) {
+            lhs = Expr::And(Box::new(lhs), Box::new(self.not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Expr, ParseError> {
+        if self.next_if(&Token::Not) {
+            Ok(Expr::Not(Box::new(self.not()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let Some((offset, token)) = self.tokens.get(self.position).cloned() else {
+            return Err(ParseError {
+                offset: self.end,
+                message: "expected a feature or `(`, found the end".to_owned(),
+            });
+        };
+        self.position += 1;
+        match token {
+            Token::Feature(feature) => Ok(Expr::Feature(feature)),
+            Token::Open => {
+                let expr = self.iff()?;
+                if self.next_if(&Token::Close) {
+                    Ok(expr)
+                } else {
+                    Err(ParseError {
+                        offset: self.peek().map_or(self.end, |&(offset, _)| offset),
+                        message: "expected `)`".to_owned(),
+                    })
+                }
+            }
+            token => Err(ParseError {
+                offset,
+                message: format!("expected a feature or `(`, found {token}"),
+            }),
+        }
+    }
+}
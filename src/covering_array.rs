@@ -0,0 +1,144 @@
+//! Greedy generation of covering arrays: sets of feature sets in which, for every `t` features,
+//! every combination of enabling and disabling them appears at least once.
+use itertools::Itertools;
+
+/// `t` features and which of their `2^t` on/off states are covered so far. Bit `i` of a state is
+/// the state of `indices[i]`.
+struct Interaction {
+    indices: Vec<usize>,
+    covered: Vec<bool>,
+}
+
+impl Interaction {
+    fn state_of(&self, row: &[bool]) -> usize {
+        self.indices
+            .iter()
+            .enumerate()
+            .filter(|(_, &index)| row[index])
+            .map(|(bit, _)| 1 << bit)
+            .sum()
+    }
+}
+
+/// Generates rows of `n` on/off states covering every `t`-wise interaction of the `n` features.
+///
+/// Only rows for which `allowed` returns `true` are generated. States of an interaction that
+/// cannot be found in an allowed row are left uncovered, so with constraints the result is a best
+/// effort.
+pub(crate) fn generate(n: usize, t: usize, allowed: impl Fn(&[bool]) -> bool) -> Vec<Vec<bool>> {
+    let t = t.min(n);
+    let mut interactions: Vec<Interaction> = (0..n)
+        .combinations(t)
+        .map(|indices| Interaction {
+            indices,
+            covered: vec![false; 1 << t],
+        })
+        .collect();
+
+    let mut rows = vec![];
+    while let Some((seed, state)) = first_uncovered(&interactions) {
+        // The features fixed by the uncovered state
+        let mut fixed = vec![None; n];
+        for (bit, &index) in interactions[seed].indices.iter().enumerate() {
+            fixed[index] = Some(state & (1 << bit) != 0);
+        }
+
+        match build_row(&interactions, &fixed, &allowed) {
+            Some(row) => {
+                for interaction in &mut interactions {
+                    let state = interaction.state_of(&row);
+                    interaction.covered[state] = true;
+                }
+                rows.push(row);
+            }
+            // No allowed row has this state, stop looking for one
+            None => interactions[seed].covered[state] = true,
+        }
+    }
+
+    rows
+}
+
+/// The index of the first interaction with an uncovered state, and that state
+fn first_uncovered(interactions: &[Interaction]) -> Option<(usize, usize)> {
+    interactions
+        .iter()
+        .enumerate()
+        .find_map(|(i, interaction)| {
+            interaction
+                .covered
+                .iter()
+                .position(|covered| !covered)
+                .map(|state| (i, state))
+        })
+}
+
+/// Completes the `fixed` states into an allowed row, preferring rows that cover many uncovered
+/// states
+fn build_row(
+    interactions: &[Interaction],
+    fixed: &[Option<bool>],
+    allowed: impl Fn(&[bool]) -> bool,
+) -> Option<Vec<bool>> {
+    let n = fixed.len();
+    let free: Vec<usize> = (0..n).filter(|&index| fixed[index].is_none()).collect();
+
+    // Greedily set every free feature to the state that covers the most uncovered states together
+    // with the features set before it. Ties keep the feature disabled.
+    let mut row: Vec<Option<bool>> = fixed.to_vec();
+    for &index in &free {
+        let gain = |on: bool| {
+            interactions
+                .iter()
+                .filter(|interaction| interaction.indices.contains(&index))
+                .filter(|interaction| {
+                    interaction
+                        .indices
+                        .iter()
+                        .all(|&other| other == index || row[other].is_some())
+                })
+                .filter(|interaction| {
+                    let state: usize = interaction
+                        .indices
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, &other)| {
+                            if other == index {
+                                on
+                            } else {
+                                row[other] == Some(true)
+                            }
+                        })
+                        .map(|(bit, _)| 1 << bit)
+                        .sum();
+                    !interaction.covered[state]
+                })
+                .count()
+        };
+        row[index] = Some(gain(true) > gain(false));
+    }
+    let greedy: Vec<bool> = row.into_iter().map(|state| state == Some(true)).collect();
+    if allowed(&greedy) {
+        return Some(greedy);
+    }
+
+    // Otherwise repair the greedy row, or a row with every free feature disabled, by flipping one
+    // or two free features
+    let minimal: Vec<bool> = fixed.iter().map(|&state| state == Some(true)).collect();
+    let flip = |base: &[bool], flipped: &[usize]| {
+        let mut row = base.to_vec();
+        for &index in flipped {
+            row[index] = !row[index];
+        }
+        row
+    };
+    std::iter::once(minimal.clone())
+        .chain(free.iter().map(|&index| flip(&greedy, &[index])))
+        .chain(free.iter().map(|&index| flip(&minimal, &[index])))
+        .chain(
+            free.iter()
+                .tuple_combinations()
+                .map(|(&a, &b)| flip(&minimal, &[a, b])),
+        )
+        .find(|row| allowed(row))
+}
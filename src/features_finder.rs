@@ -1,9 +1,10 @@
 use crate::{
-    cargo_metadata::Dependency,
+    cargo_metadata::{Dependency, Strategy},
+    covering_array,
     types::{Feature, FeatureList},
 };
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 
 pub fn fetch_feature_sets(package: &crate::cargo_metadata::Package) -> Vec<FeatureList> {
     let mut features = FeatureList::default();
@@ -70,30 +71,153 @@ pub fn fetch_feature_sets(package: &crate::cargo_metadata::Package) -> Vec<Featu
 
     let mut feature_sets = vec![];
 
-    let max_combination_size = package.max_combination_size.unwrap_or(features.len());
-    for n in 0..=max_combination_size {
-        'outer: for feature_set in features.iter().combinations(n) {
-            'inner: for skip_feature_set in &package.skip_feature_sets {
-                for feature in skip_feature_set.iter() {
-                    if !feature_set.contains(&feature) {
-                        // skip_feature_set does not match
-                        continue 'inner;
+    match package.strategy {
+        Strategy::Powerset => {
+            let max_combination_size = package.max_combination_size.unwrap_or(features.len());
+            for n in 0..=max_combination_size {
+                for feature_set in features.iter().combinations(n) {
+                    if !is_skipped(package, &feature_set) {
+                        feature_sets.push(feature_set);
                     }
                 }
-                // skip_feature_set matches: do not add it to feature_sets
-                continue 'outer;
             }
-            feature_sets.push(
-                feature_set
-                    .into_iter()
-                    .chain(package.always_include_features.iter())
-                    .cloned()
-                    .collect(),
-            );
+        }
+        Strategy::TWise(t) => {
+            let to_feature_set = |row: &[bool]| -> Vec<&Feature> {
+                features
+                    .iter()
+                    .zip(row)
+                    .filter(|(_, &on)| on)
+                    .map(|(feature, _)| feature)
+                    .collect()
+            };
+            let mut rows = covering_array::generate(features.len(), t, |row| {
+                let feature_set = to_feature_set(row);
+                package
+                    .max_combination_size
+                    .is_none_or(|max| feature_set.len() <= max)
+                    && !is_skipped(package, &feature_set)
+            });
+            rows.sort_by_key(|row| row.iter().filter(|&&on| on).count());
+            feature_sets.extend(rows.iter().map(|row| to_feature_set(row)));
         }
     }
 
+    // Feature sets that enable the same features in the end result in identical builds, e.g.
+    // [B] and [A, B] if B = ["A"]. Only keep the first of them.
+    let mut seen = HashSet::new();
     feature_sets
+        .into_iter()
+        .map(|feature_set| -> FeatureList {
+            feature_set
+                .into_iter()
+                .chain(package.always_include_features.iter())
+                .cloned()
+                .collect()
+        })
+        .filter(|feature_set| seen.insert(resolve_features(package, feature_set.iter())))
+        .collect()
+}
+
+/// Everything that enabling `feature_set` enables in the end: the features themselves, the
+/// features they imply through `[features]` and the optional dependencies and dependency features
+/// those enable
+fn resolve_features<'a>(
+    package: &'a crate::cargo_metadata::Package,
+    feature_set: impl IntoIterator<Item = &'a Feature>,
+) -> BTreeSet<String> {
+    let mut resolved = BTreeSet::new();
+    let mut pending: Vec<&str> = feature_set.into_iter().map(|feature| &**feature).collect();
+
+    while let Some(feature) = pending.pop() {
+        if !resolved.insert(feature.to_owned()) {
+            continue;
+        }
+        let Some(implied_features) = package.feature_map.get(feature) else {
+            // An optional dependency without a feature of its own
+            continue;
+        };
+        for implied in implied_features.iter() {
+            if implied.starts_with("dep:") {
+                resolved.insert(implied.to_string());
+            } else if let Some((dependency, _)) = implied.split_once('/') {
+                resolved.insert(implied.to_string());
+                // `dependency?/feature` does not enable the dependency itself
+                if !dependency.ends_with('?') {
+                    pending.push(dependency);
+                }
+            } else {
+                pending.push(implied);
+            }
+        }
+    }
+
+    resolved
+}
+
+/// Whether a feature set matches one of the `skip_feature_sets`, enables more than one feature of
+/// a `mutually_exclusive_features` group or does not enable exactly one feature of a
+/// `require_one_of` group, enables a feature without what it requires in `feature_requires` or
+/// violates one of the `constraints`. Features count as enabled if they are enabled through other
+/// features.
+pub(crate) fn is_skipped(
+    package: &crate::cargo_metadata::Package,
+    feature_set: &[&Feature],
+) -> bool {
+    let resolved = resolve_features(
+        package,
+        feature_set
+            .iter()
+            .copied()
+            .chain(package.always_include_features.iter()),
+    );
+    for group in &package.mutually_exclusive_features {
+        let enabled = group
+            .iter()
+            .filter(|feature| resolved.contains(&***feature))
+            .count();
+        if enabled > 1 {
+            return true;
+        }
+    }
+    for group in &package.require_one_of {
+        let enabled = group
+            .iter()
+            .filter(|feature| resolved.contains(&***feature))
+            .count();
+        if enabled != 1 {
+            return true;
+        }
+    }
+    for (feature, requirements) in &package.feature_requires {
+        if resolved.contains(&**feature)
+            && !requirements
+                .iter()
+                .all(|requirement| resolved.contains(&**requirement))
+        {
+            return true;
+        }
+    }
+    let enabled = |feature: &str| resolved.contains(feature);
+    if !package
+        .constraints
+        .iter()
+        .all(|constraint| constraint.eval(&enabled))
+    {
+        return true;
+    }
+
+    'outer: for skip_feature_set in &package.skip_feature_sets {
+        for feature in skip_feature_set.iter() {
+            if !feature_set.contains(&feature) {
+                // skip_feature_set does not match
+                continue 'outer;
+            }
+        }
+        // skip_feature_set matches: do not add it to feature_sets
+        return true;
+    }
+    false
 }
 
 fn fetch_optional_dependencies(
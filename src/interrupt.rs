@@ -0,0 +1,89 @@
+//! Handling of Ctrl-C: the interrupt is forwarded to the running cargo processes, no further
+//! combinations are started, and a partial summary is printed once they have exited.
+use std::{
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The process ids of the running cargo processes
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(vec![]);
+
+/// Handles Ctrl-C from now on. A second Ctrl-C exits immediately.
+pub(crate) fn install_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        for &pid in CHILDREN
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            forward(pid);
+        }
+    })
+}
+
+/// Whether Ctrl-C was pressed
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Whether a cargo process exiting with `status` was interrupted. With a terminal, cargo receives
+/// SIGINT itself and can exit before the handler has run.
+pub(crate) fn interrupted_process(status: process::ExitStatus) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if status.signal() == Some(libc::SIGINT) {
+            return true;
+        }
+    }
+    !status.success() && interrupted()
+}
+
+/// Registers a running cargo process so that Ctrl-C is forwarded to it, until dropped
+pub(crate) struct RunningChild(u32);
+
+impl RunningChild {
+    pub(crate) fn new(child: &process::Child) -> Self {
+        let pid = child.id();
+        CHILDREN
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(pid);
+        // Ctrl-C may have been pressed while it was being spawned
+        if interrupted() {
+            forward(pid);
+        }
+        RunningChild(pid)
+    }
+}
+
+impl Drop for RunningChild {
+    fn drop(&mut self) {
+        CHILDREN
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|&pid| pid != self.0);
+    }
+}
+
+#[cfg(unix)]
+fn forward(pid: u32) {
+    // A terminal sends SIGINT to every process of the foreground process group, but e.g. a CI
+    // runner cancelling a job only to cargo-all-features
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGINT);
+    }
+}
+
+#[cfg(not(unix))]
+fn forward(_pid: u32) {
+    // Ctrl-C reaches every process attached to the console
+}
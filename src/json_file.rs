@@ -0,0 +1,37 @@
+//! The JSON files that cargo-all-features keeps under the target directory between runs
+use std::{error, fs, io, path};
+
+/// Reads the JSON file at `path` with `parse`, which returns `None` if the JSON is malformed. A
+/// missing file reads as the default value.
+pub(crate) fn read<T: Default>(
+    path: &path::Path,
+    parse: impl FnOnce(&json::JsonValue) -> Option<T>,
+) -> Result<T, Box<dyn error::Error>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(err) => return Err(format!("Could not read {}: {err}", path.display()).into()),
+    };
+    let malformed = || format!("{} is malformed", path.display());
+    let json = json::parse(&text).map_err(|err| format!("{}: {err}", malformed()))?;
+    Ok(parse(&json).ok_or_else(malformed)?)
+}
+
+/// Writes `json` to the file at `path`, creating its directory if needed
+pub(crate) fn write(path: &path::Path, json: json::JsonValue) -> io::Result<()> {
+    fs::create_dir_all(path.parent().expect("the file is in a directory"))?;
+    // Written to a temporary file first so that an interrupted write doesn't lose the previous
+    // contents
+    let temporary = path.with_extension("json.tmp");
+    fs::write(&temporary, json::stringify_pretty(json, 2))?;
+    fs::rename(temporary, path)
+}
+
+/// Locks the JSON file at `path` until the returned lock file is dropped, so that another process
+/// updating it waits for this one to finish reading and writing it
+pub(crate) fn lock(path: &path::Path) -> io::Result<fs::File> {
+    fs::create_dir_all(path.parent().expect("the file is in a directory"))?;
+    let lock = fs::File::create(path.with_extension("json.lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
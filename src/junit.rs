@@ -0,0 +1,151 @@
+use crate::{Combination, CombinationRun, TestOutcome};
+use std::{fmt::Write as _, fs, io, path};
+
+/// Writes a JUnit XML report to `path`, with one test suite per package and one test case per
+/// feature combination. Combinations that were never run or were interrupted are reported as
+/// skipped.
+pub(crate) fn write_report(
+    path: &path::Path,
+    command: &str,
+    combinations: &[Combination],
+    runs: &[Option<CombinationRun>],
+) -> io::Result<()> {
+    let mut packages: Vec<&str> = vec![];
+    for combination in combinations {
+        if !packages.contains(&combination.package.name.as_str()) {
+            packages.push(&combination.package.name);
+        }
+    }
+
+    let mut suites = String::new();
+    let (mut total_tests, mut total_failures, mut total_time) = (0, 0, 0.0);
+    for package in packages {
+        let cases: Vec<_> = combinations
+            .iter()
+            .zip(runs)
+            .filter(|(combination, _)| combination.package.name == package)
+            .collect();
+        let failures = cases
+            .iter()
+            .filter(
+                |(_, run)| matches!(run, Some(run) if matches!(run.outcome, TestOutcome::Fail(_))),
+            )
+            .count();
+        let skipped = cases
+            .iter()
+            .filter(|(_, run)| {
+                matches!(
+                    run,
+                    None | Some(CombinationRun {
+                        outcome: TestOutcome::Interrupted,
+                        ..
+                    })
+                )
+            })
+            .count();
+        let time: f64 = cases
+            .iter()
+            .filter_map(|(_, run)| run.as_ref())
+            .map(|run| run.duration.as_secs_f64())
+            .sum();
+
+        total_tests += cases.len();
+        total_failures += failures;
+        total_time += time;
+
+        let _ = writeln!(
+            suites,
+            r#"  <testsuite name="{}" tests="{}" failures="{}" skipped="{}" time="{:.3}">"#,
+            escape(package),
+            cases.len(),
+            failures,
+            skipped,
+            time
+        );
+        for (combination, run) in cases {
+            write_test_case(&mut suites, command, combination, run.as_ref());
+        }
+        suites.push_str("  </testsuite>\n");
+    }
+
+    let report = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+            "\n",
+            r#"<testsuites name="cargo all-features {}" tests="{}" failures="{}" time="{:.3}">"#,
+            "\n{}</testsuites>\n"
+        ),
+        escape(command),
+        total_tests,
+        total_failures,
+        total_time,
+        suites
+    );
+
+    fs::write(path, report)
+}
+
+fn write_test_case(
+    xml: &mut String,
+    command: &str,
+    combination: &Combination,
+    run: Option<&CombinationRun>,
+) {
+    let name = escape(&format!("features=[{}]", combination.feature_set));
+    let classname = escape(&combination.package.name);
+
+    let Some(run) = run else {
+        let _ = writeln!(
+            xml,
+            r#"    <testcase name="{name}" classname="{classname}"><skipped message="not run"/></testcase>"#
+        );
+        return;
+    };
+
+    let _ = write!(
+        xml,
+        r#"    <testcase name="{name}" classname="{classname}" time="{:.3}">"#,
+        run.duration.as_secs_f64()
+    );
+    if run.outcome == TestOutcome::Interrupted {
+        xml.push_str(r#"<skipped message="interrupted"/>"#);
+    }
+    if let TestOutcome::Fail(status) = &run.outcome {
+        let _ = write!(
+            xml,
+            "\n      <failure message=\"{}\">{}</failure>\n      <system-out>{}</system-out>\n    ",
+            escape(&format!("cargo {command} failed: {status}")),
+            escape(&run.stderr),
+            escape(&run.stdout)
+        );
+    }
+    xml.push_str("</testcase>\n");
+}
+
+/// Escapes text for use in XML content and attributes. Terminal color codes and other characters
+/// that are not allowed in XML are dropped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\u{1b}' if chars.peek() == Some(&'[') => {
+                // Skip an ANSI escape sequence up to and including its final byte
+                for c in chars.by_ref().skip(1) {
+                    if ('\u{40}'..='\u{7e}').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -1,10 +1,29 @@
 use clap::{error::ErrorKind, Command, Parser, ValueEnum};
-use std::{env, error, ffi, process};
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    env, error, ffi, fmt, path, process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time,
+};
 
+mod cache;
 pub mod cargo_metadata;
+pub mod constraints;
+mod covering_array;
 pub mod features_finder;
+mod interrupt;
+mod json_file;
+mod junit;
+mod minimizer;
+mod report;
+mod state;
 pub mod test_runner;
 mod types;
+mod validation;
 
 #[derive(Parser, Clone)]
 #[command(author, version, about = "See https://crates.io/crates/cargo-all-features", long_about = None)]
@@ -50,6 +69,137 @@ struct Cli {
     )]
     chunk_granularity: ChunkGranularity,
 
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_name = "N",
+        help = "Number of feature combinations to run concurrently, each with its own target directory. Unlike `--jobs`, which is passed down to cargo"
+    )]
+    parallel: usize,
+
+    #[arg(
+        long,
+        help = "Run every feature combination even if some fail, then print a summary of all outcomes. Unlike `--keep-going`, which is passed down to cargo"
+    )]
+    continue_on_failure: bool,
+
+    #[arg(
+        long,
+        help = "Like `--continue-on-failure`, and also passed down to `cargo test` and `cargo bench` so that they run every test binary"
+    )]
+    no_fail_fast: bool,
+
+    #[arg(
+        long,
+        help = "Print the feature combinations that would be run, without running cargo"
+    )]
+    list: bool,
+
+    #[arg(
+        long,
+        help = "Print the effective configuration of every package, including workspace defaults, without running cargo"
+    )]
+    print_config: bool,
+
+    #[arg(
+        long = "message-format",
+        value_name = "FMT",
+        help = "Output format: `human` for colored text, `json` for one JSON object per line on stdout (cargo's own output goes to stderr). Other formats are passed down to cargo"
+    )]
+    message_format_arg: Option<String>,
+
+    /// The format of `--message-format` if it is one of cargo-all-features
+    #[arg(skip)]
+    message_format: MessageFormat,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write a JUnit XML report with one test case per feature combination to PATH"
+    )]
+    junit: Option<path::PathBuf>,
+
+    #[arg(
+        long,
+        help = "Reduce every failing feature combination to a minimal set of features that still fails"
+    )]
+    minimize: bool,
+
+    #[arg(
+        long,
+        help = "Skip the feature combinations that passed in the previous run with the same cargo command and arguments"
+    )]
+    resume: bool,
+
+    #[arg(
+        long,
+        help = "Only run the feature combinations that failed in the previous run with the same cargo command and arguments"
+    )]
+    failed: bool,
+
+    #[arg(
+        long,
+        help = "Skip the feature combinations that passed before with unchanged sources, Cargo.lock, toolchain and cargo arguments"
+    )]
+    cache: bool,
+
+    #[command(flatten)]
+    manifest: clap_cargo::Manifest,
+
+    #[command(flatten)]
+    workspace: clap_cargo::Workspace,
+
+    #[arg(
+        long,
+        value_name = "NAME",
+        help = "Use the matrix profile NAME from `profiles` in the metadata. If no package defines it, `--profile NAME` is passed down to cargo"
+    )]
+    profile: Option<String>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "FEATURES",
+        help = "Exclude these comma-separated features from the build matrix, replacing `denylist`"
+    )]
+    denylist: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "FEATURES",
+        help = "Only include these comma-separated features in the build matrix, replacing `allowlist`"
+    )]
+    allowlist: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        value_name = "FEATURES",
+        help = "Always include these comma-separated features, replacing `always_include_features`"
+    )]
+    always_include: Option<Vec<String>>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "The maximum number of features to try at once, replacing `max_combination_size`"
+    )]
+    max_combination_size: Option<usize>,
+
+    #[arg(
+        long,
+        help = "Skip optional dependencies, like `skip_optional_dependencies = true`"
+    )]
+    skip_optional_dependencies: bool,
+
+    #[arg(
+        long,
+        value_name = "FEATURES",
+        help = "Skip feature sets including these comma-separated features, in addition to `skip_feature_sets`. Can be given multiple times"
+    )]
+    skip_feature_set: Vec<String>,
+
     #[arg(
         help = "arguments to pass down to cargo",
         allow_hyphen_values = true,
@@ -64,12 +214,37 @@ enum ChunkGranularity {
     Feature,
 }
 
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
 #[derive(Clone, Debug)]
 enum WorkItem {
     PackageOnly(cargo_metadata::Package),
     PackageFeature(cargo_metadata::Package, types::FeatureList),
 }
 
+/// A single feature set of a package to run the cargo command with
+#[derive(Clone, Debug)]
+struct Combination<'a> {
+    package: &'a cargo_metadata::Package,
+    feature_set: types::FeatureList,
+}
+
+/// The result of running the cargo command with a [`Combination`]
+struct CombinationRun {
+    outcome: TestOutcome,
+    duration: time::Duration,
+    /// Only captured when needed for a report
+    stdout: String,
+    stderr: String,
+    /// Whether the outcome was taken from the cache instead of running cargo
+    cached: bool,
+}
+
 pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling::Styles::styled()
     .header(clap_cargo::style::HEADER)
     .usage(clap_cargo::style::USAGE)
@@ -91,14 +266,15 @@ pub fn run() -> Result<(), Box<dyn error::Error>> {
     // Backward compatibility.
     // Check if older commands is used, use cli.cargo_command as an argument, and extract the
     // command.
-    // Otherwise, a command should be provided for `cargo all-features <command>`
+    // Otherwise, a command should be provided for `cargo all-features <command>`, unless the
+    // feature matrix is only listed
     let cargo_command = if let Some(cargo_command) = cargo_command.strip_suffix("-all-features") {
-        if let Some(arg) = cli.cargo_command {
+        if let Some(arg) = cli.cargo_command.take() {
             cli.cargo_args.insert(0, arg);
         }
-        cargo_command.into()
+        Some(cargo_command.into())
     } else {
-        if cli.cargo_command.is_none() {
+        if cli.cargo_command.is_none() && !cli.list && !cli.print_config {
             cmd.error(
                 ErrorKind::InvalidValue,
                 "A cargo command is needed, e.g. check, test, build, clippy and ...",
@@ -106,9 +282,31 @@ pub fn run() -> Result<(), Box<dyn error::Error>> {
             .print()?;
             process::exit(1);
         }
-        cli.cargo_command.unwrap()
+        cli.cargo_command.take()
     };
 
+    // Backward compatibility: `--message-format` used to be passed down to cargo, keep doing so
+    // for the formats that aren't those of cargo-all-features, e.g. `short`
+    if let Some(format) = cli.message_format_arg.take() {
+        match MessageFormat::from_str(&format, false) {
+            Ok(message_format) => cli.message_format = message_format,
+            Err(_) => {
+                cli.cargo_args
+                    .splice(0..0, ["--message-format".to_owned(), format]);
+            }
+        }
+    }
+
+    // Backward compatibility: `--no-fail-fast` used to be passed down to `cargo test`, keep doing
+    // so while also running every feature combination. Other commands, e.g. `cargo check`, reject
+    // it.
+    if cli.no_fail_fast {
+        cli.continue_on_failure = true;
+        if matches!(cargo_command.as_deref(), Some("test" | "bench")) {
+            cli.cargo_args.insert(0, "--no-fail-fast".to_owned());
+        }
+    }
+
     if cli.chunk > cli.n_chunks || cli.chunk < 1 {
         cmd.error(
             ErrorKind::InvalidValue,
@@ -124,7 +322,39 @@ pub fn run() -> Result<(), Box<dyn error::Error>> {
         process::exit(1)
     }
 
-    let packages = determine_packages_to_test()?;
+    if cli.parallel == 0 {
+        cmd.error(ErrorKind::InvalidValue, "--parallel must be at least 1")
+            .print()?;
+        process::exit(1)
+    }
+
+    let metadata = cargo_metadata::fetch(
+        cli.manifest.manifest_path.as_deref(),
+        cargo_command.as_deref(),
+        cli.profile.as_deref(),
+        &config_overrides(&cli),
+    )?;
+
+    // Backward compatibility: `--profile` used to be passed down to cargo, keep doing so unless it
+    // names a matrix profile
+    if let Some(profile) = cli.profile.take() {
+        if !metadata.profiles.contains(&profile) {
+            cli.cargo_args
+                .splice(0..0, ["--profile".to_owned(), profile]);
+        }
+    }
+    let packages = determine_packages_to_test(&cli, &metadata)?;
+
+    if cli.print_config {
+        let mut config = json::JsonValue::new_object();
+        for package in &packages {
+            config[package.name.as_str()] = package.config_to_json();
+        }
+        println!("{}", json::stringify_pretty(config, 4));
+        if !cli.list {
+            return Ok(());
+        }
+    }
 
     // Build the list of work items. If split_by_feature is set, expand each package into
     // (package, feature-set) tuples. Otherwise operate on packages as a whole.
@@ -153,12 +383,16 @@ pub fn run() -> Result<(), Box<dyn error::Error>> {
     let chunk = if let Some(chunk) = work_items.chunks(chunk_size).nth(cli.chunk - 1) {
         chunk
     } else {
-        println!("Chunk is empty (did you ask for more chunks than there are packages?");
+        let message = "Chunk is empty (did you ask for more chunks than there are packages?";
+        match cli.message_format {
+            MessageFormat::Human => println!("{message}"),
+            MessageFormat::Json => eprintln!("{message}"),
+        }
         return Ok(());
     };
-    if cli.n_chunks != 1 {
+    if cli.n_chunks != 1 && cli.message_format == MessageFormat::Human {
         print_chunk_info(
-            cli.chunk_granularity,
+            cli.chunk_granularity.clone(),
             cli.chunk,
             cli.n_chunks,
             chunk_size,
@@ -166,20 +400,123 @@ pub fn run() -> Result<(), Box<dyn error::Error>> {
         );
     }
 
-    for item in chunk {
-        let outcome = match item {
-            WorkItem::PackageOnly(package) => {
-                test_all_features_for_package(package, cargo_command.clone(), &cli.cargo_args)
-            }
-            WorkItem::PackageFeature(package, feature_set) => test_one_feature_for_package(
+    let mut combinations: Vec<Combination> = chunk
+        .iter()
+        .flat_map(|item| match item {
+            WorkItem::PackageOnly(package) => features_finder::fetch_feature_sets(package)
+                .into_iter()
+                .map(|feature_set| Combination {
+                    package,
+                    feature_set,
+                })
+                .collect::<Vec<_>>(),
+            WorkItem::PackageFeature(package, feature_set) => vec![Combination {
                 package,
-                feature_set,
-                cargo_command.clone(),
+                feature_set: feature_set.clone(),
+            }],
+        })
+        .collect();
+
+    // The state is only needed to run cargo, or to list what `--resume` or `--failed` would run
+    let needs_state = !cli.list || cli.resume || cli.failed;
+    let state = cargo_command
+        .as_deref()
+        .filter(|_| needs_state)
+        .map(|command| {
+            state::State::load(
+                &metadata.target_directory,
+                &metadata.workspace_root,
+                command,
                 &cli.cargo_args,
-            ),
-        }?;
-        if let TestOutcome::Fail(exit_status) = outcome {
-            process::exit(exit_status.code().unwrap());
+            )
+        });
+    if let Some(state) = state.as_ref().filter(|_| cli.resume) {
+        let total = combinations.len();
+        combinations.retain(|combination| !state.passed(combination));
+        // `--list` prints nothing but the combinations
+        if !cli.list {
+            report::print_resumed(total - combinations.len(), cli.message_format);
+        }
+    }
+    if let Some(state) = state.as_ref().filter(|_| cli.failed) {
+        combinations.retain(|combination| state.failed(combination));
+        if !cli.list {
+            report::print_rerunning_failed(combinations.len(), cli.message_format);
+        }
+    }
+
+    if cli.message_format == MessageFormat::Json {
+        report::print_json_matrix(&combinations);
+    }
+
+    if cli.list {
+        if cli.message_format == MessageFormat::Human {
+            print_feature_matrix(&combinations);
+        }
+        return Ok(());
+    }
+
+    // Safety: the cargo command is only optional when listing the feature matrix or printing the
+    // configuration
+    let cargo_command = cargo_command.unwrap();
+    let mut state = state.unwrap();
+    if !cli.resume && !cli.failed {
+        state.clear()?;
+    }
+
+    let cache = if cli.cache {
+        let packages = combinations.iter().map(|combination| combination.package);
+        let cache = cache::Cache::load(&metadata, packages, &cargo_command, &cli.cargo_args)?;
+        Some(Mutex::new(cache))
+    } else {
+        None
+    };
+
+    interrupt::install_handler()?;
+    let runs = test_combinations(
+        &combinations,
+        &cargo_command,
+        &cli,
+        &metadata.target_directory,
+        &Mutex::new(state),
+        cache.as_ref(),
+    )?;
+
+    // Ctrl-C may have reached cargo before the handler
+    let mut interrupted = interrupt::interrupted()
+        || runs
+            .iter()
+            .flatten()
+            .any(|run| run.outcome == TestOutcome::Interrupted);
+    if cli.minimize && !interrupted {
+        interrupted = minimize_failures(
+            &combinations,
+            &runs,
+            &cargo_command,
+            &cli,
+            &metadata.target_directory,
+        )?;
+    }
+
+    if let Some(junit) = &cli.junit {
+        junit::write_report(junit, &cargo_command, &combinations, &runs)?;
+    }
+
+    match cli.message_format {
+        MessageFormat::Human if cli.continue_on_failure || interrupted => {
+            report::print_summary(&combinations, &runs)
+        }
+        MessageFormat::Human => (),
+        MessageFormat::Json => report::print_json_summary(&runs),
+    }
+
+    if interrupted {
+        // 128 plus SIGINT, as shells do
+        process::exit(130);
+    }
+    for run in runs.into_iter().flatten() {
+        if let TestOutcome::Fail(status) = run.outcome {
+            process::exit(status.exit_code());
         }
     }
 
@@ -234,38 +571,241 @@ fn print_chunk_info(
     );
 }
 
-fn test_all_features_for_package(
-    package: &cargo_metadata::Package,
-    command: String,
-    cargo_args: &[String],
-) -> Result<TestOutcome, Box<dyn error::Error>> {
-    let feature_sets = crate::features_finder::fetch_feature_sets(package);
-
-    for feature_set in feature_sets {
-        let outcome =
-            test_one_feature_for_package(package, &feature_set, command.clone(), cargo_args)?;
-
-        match outcome {
-            TestOutcome::Pass => (),
-            // Fail fast if we encounter a test failure
-            t @ TestOutcome::Fail(_) => return Ok(t),
+fn print_feature_matrix(combinations: &[Combination]) {
+    for combination in combinations {
+        println!(
+            "crate={} features=[{}]",
+            combination.package.name, combination.feature_set
+        );
+    }
+}
+
+/// Runs the cargo command for every combination, `cli.parallel` of them at a time.
+///
+/// Unless `cli.continue_on_failure` is set, stops starting new combinations once one fails.
+/// Combinations that were never started have no run.
+fn test_combinations(
+    combinations: &[Combination],
+    command: &str,
+    cli: &Cli,
+    target_directory: &path::Path,
+    state: &Mutex<state::State>,
+    cache: Option<&Mutex<cache::Cache>>,
+) -> Result<Vec<Option<CombinationRun>>, Box<dyn error::Error>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.parallel)
+        .build()?;
+    let failed = AtomicBool::new(false);
+
+    pool.install(|| {
+        combinations
+            .par_iter()
+            .map(|combination| {
+                if failed.load(Ordering::SeqCst) || interrupt::interrupted() {
+                    return Ok(None);
+                }
+
+                let cached = cache.is_some_and(|cache| cache.lock().unwrap().passed(combination));
+                let run = if cached {
+                    report::print_cached(combination, command, cli.message_format);
+                    CombinationRun {
+                        outcome: TestOutcome::Pass,
+                        duration: time::Duration::ZERO,
+                        stdout: String::new(),
+                        stderr: String::new(),
+                        cached: true,
+                    }
+                } else {
+                    let target_dir = job_target_dir(
+                        cli,
+                        target_directory,
+                        rayon::current_thread_index().unwrap_or_default(),
+                    );
+
+                    if cli.message_format == MessageFormat::Json {
+                        report::print_json_started(combination);
+                    }
+
+                    test_one_feature_for_package(
+                        combination.package,
+                        &combination.feature_set,
+                        command.to_owned(),
+                        cli,
+                        target_dir.as_deref(),
+                    )?
+                };
+
+                if cli.message_format == MessageFormat::Json {
+                    report::print_json_finished(combination, &run);
+                }
+
+                if let TestOutcome::Fail(_) = run.outcome {
+                    state.lock().unwrap().record_failure(combination)?;
+                }
+                if run.outcome == TestOutcome::Pass {
+                    state.lock().unwrap().record_pass(combination)?;
+                    if let Some(cache) = cache.filter(|_| !run.cached) {
+                        cache.lock().unwrap().record_pass(combination)?;
+                    }
+                }
+
+                if !cli.continue_on_failure && matches!(run.outcome, TestOutcome::Fail(_)) {
+                    // Fail fast if we encounter a test failure
+                    failed.store(true, Ordering::SeqCst);
+                }
+                if run.outcome == TestOutcome::Interrupted {
+                    // Ctrl-C may have reached cargo before the handler
+                    failed.store(true, Ordering::SeqCst);
+                }
+
+                Ok(Some(run))
+            })
+            .collect::<Result<Vec<_>, Box<dyn error::Error + Send + Sync>>>()
+    })
+    .map_err(|err| err as Box<dyn error::Error>)
+}
+
+/// The metadata options set on the command line, which override those of the workspace members
+fn config_overrides(cli: &Cli) -> json::JsonValue {
+    let mut overrides = json::JsonValue::new_object();
+    if let Some(denylist) = &cli.denylist {
+        overrides["denylist"] = denylist.clone().into();
+    }
+    if let Some(allowlist) = &cli.allowlist {
+        overrides["allowlist"] = allowlist.clone().into();
+    }
+    if let Some(always_include) = &cli.always_include {
+        overrides["always_include_features"] = always_include.clone().into();
+    }
+    if let Some(max_combination_size) = cli.max_combination_size {
+        overrides["max_combination_size"] = max_combination_size.into();
+    }
+    if cli.skip_optional_dependencies {
+        overrides["skip_optional_dependencies"] = true.into();
+    }
+    if !cli.skip_feature_set.is_empty() {
+        overrides["skip_feature_sets"] = cli
+            .skip_feature_set
+            .iter()
+            .map(|set| set.split(',').collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+            .into();
+    }
+    overrides
+}
+
+/// With a single job cargo can use the default target directory, otherwise every job gets a
+/// target directory of its own
+fn job_target_dir(cli: &Cli, target_directory: &path::Path, slot: usize) -> Option<path::PathBuf> {
+    (cli.parallel > 1).then(|| {
+        target_directory
+            .join("cargo-all-features")
+            .join(format!("job-{slot}"))
+    })
+}
+
+/// Delta debugs every failing combination down to a minimal failing feature set and reports it.
+///
+/// Features from `always_include_features` are kept in every feature set that is tried. Returns
+/// whether it stopped because cargo was interrupted.
+fn minimize_failures(
+    combinations: &[Combination],
+    runs: &[Option<CombinationRun>],
+    command: &str,
+    cli: &Cli,
+    target_directory: &path::Path,
+) -> Result<bool, Box<dyn error::Error>> {
+    // Whether a feature set of a package failed, shared between minimizations to avoid running
+    // the same feature set twice
+    let mut failed: HashMap<(&str, Vec<types::Feature>), bool> = HashMap::new();
+    fn key<'a>(
+        package: &'a cargo_metadata::Package,
+        feature_set: &types::FeatureList,
+    ) -> (&'a str, Vec<types::Feature>) {
+        let mut features = feature_set.to_vec();
+        features.sort();
+        (package.name.as_str(), features)
+    }
+    for (combination, run) in combinations.iter().zip(runs) {
+        if let Some(run) = run {
+            if run.outcome != TestOutcome::Interrupted {
+                failed.insert(
+                    key(combination.package, &combination.feature_set),
+                    run.outcome != TestOutcome::Pass,
+                );
+            }
         }
     }
 
-    Ok(TestOutcome::Pass)
+    let target_dir = job_target_dir(cli, target_directory, 0);
+    let mut interrupted = false;
+    for (combination, _) in combinations
+        .iter()
+        .zip(runs)
+        .filter(|(_, run)| matches!(run, Some(run) if matches!(run.outcome, TestOutcome::Fail(_))))
+    {
+        let package = combination.package;
+        let always = &package.always_include_features;
+        let with_always = |features: &[types::Feature]| -> types::FeatureList {
+            features.iter().chain(always.iter()).cloned().collect()
+        };
+        let candidates: Vec<_> = combination
+            .feature_set
+            .iter()
+            .filter(|feature| !always.contains(feature))
+            .cloned()
+            .collect();
+
+        let minimal = minimizer::minimize(&candidates, |features| {
+            let feature_set = with_always(features);
+            if features_finder::is_skipped(package, &feature_set.iter().collect::<Vec<_>>()) {
+                // Not a feature set that is supposed to work, e.g. one without any of the
+                // features in a `require_one_of` group
+                return Ok(false);
+            }
+            if let Some(&failed) = failed.get(&key(package, &feature_set)) {
+                return Ok(failed);
+            }
+            let run = test_one_feature_for_package(
+                package,
+                &feature_set,
+                command.to_owned(),
+                cli,
+                target_dir.as_deref(),
+            )?;
+            if run.outcome == TestOutcome::Interrupted {
+                interrupted = true;
+                return Err("interrupted".into());
+            }
+            let fails = run.outcome != TestOutcome::Pass;
+            failed.insert(key(package, &feature_set), fails);
+            Ok::<_, Box<dyn error::Error + Send + Sync>>(fails)
+        });
+        let minimal = match minimal {
+            Ok(minimal) => minimal,
+            // Stop minimizing, the partial summary is printed next
+            Err(_) if interrupted => return Ok(true),
+            Err(err) => return Err(err),
+        };
+
+        report::print_minimized(combination, &with_always(&minimal), cli.message_format);
+    }
+
+    Ok(false)
 }
 
 fn test_one_feature_for_package(
     package: &cargo_metadata::Package,
     feature_set: &types::FeatureList,
     command: String,
-    cargo_args: &[String],
-) -> Result<TestOutcome, Box<dyn error::Error>> {
+    cli: &Cli,
+    target_dir: Option<&path::Path>,
+) -> Result<CombinationRun, Box<dyn error::Error + Send + Sync>> {
     let mut test_runner = crate::test_runner::TestRunner::new(
-        command.clone(),
+        command,
         package.name.clone(),
         feature_set.clone(),
-        cargo_args,
+        &cli.cargo_args,
         package
             .manifest_path
             .parent()
@@ -273,29 +813,162 @@ fn test_one_feature_for_package(
             .to_owned(),
     );
 
-    test_runner.run()
-}
+    test_runner.message_format(cli.message_format);
 
-fn determine_packages_to_test() -> Result<Vec<cargo_metadata::Package>, Box<dyn error::Error>> {
-    let current_dir = env::current_dir()?;
-    let metadata = cargo_metadata::fetch()?;
+    if let Some(target_dir) = target_dir {
+        // Output of concurrent runs would interleave
+        test_runner
+            .target_dir(target_dir)
+            .output_mode(test_runner::OutputMode::Buffer);
+    } else if cli.junit.is_some() {
+        test_runner.output_mode(test_runner::OutputMode::Tee);
+    }
+
+    let start = time::Instant::now();
+    let outcome = test_runner.run()?;
 
-    Ok(if current_dir == metadata.workspace_root {
-        metadata
-            .packages
+    Ok(CombinationRun {
+        outcome,
+        duration: start.elapsed(),
+        stdout: String::from_utf8_lossy(test_runner.captured_stdout()).into_owned(),
+        stderr: String::from_utf8_lossy(test_runner.captured_stderr()).into_owned(),
+        cached: false,
+    })
+}
+
+/// Selects the packages to test like cargo does, with `--package`, `--workspace` and `--exclude`.
+///
+/// By default, every member of the workspace is tested when run from the workspace root, or from
+/// a directory that doesn't belong to a member, and only the member in whose directory it is run
+/// otherwise. With `--manifest-path`, the directory of the manifest is used instead of the current
+/// one. Packages with `skip_package` are only tested when selected with `--package` or by the
+/// directory.
+fn determine_packages_to_test(
+    cli: &Cli,
+    metadata: &cargo_metadata::Metadata,
+) -> Result<Vec<cargo_metadata::Package>, Box<dyn error::Error>> {
+    let members: Vec<&cargo_metadata::Package> = metadata
+        .packages
+        .iter()
+        .filter(|package| metadata.workspace_members.contains(&package.id))
+        .collect();
+    let all_members = || {
+        members
             .iter()
-            .filter(|package| metadata.workspace_members.contains(&package.id))
+            .copied()
             .filter(|package| !package.skip_package)
-            .cloned()
-            .collect::<Vec<cargo_metadata::Package>>()
+            .collect::<Vec<_>>()
+    };
+
+    let selected: Vec<&cargo_metadata::Package> = if !cli.workspace.package.is_empty() {
+        let unmatched: Vec<&str> = cli
+            .workspace
+            .package
+            .iter()
+            .filter(|pattern| {
+                !members
+                    .iter()
+                    .any(|package| glob_match(pattern, &package.name))
+            })
+            .map(String::as_str)
+            .collect();
+        if !unmatched.is_empty() {
+            return Err(format!(
+                "package(s) `{}` not found in workspace `{}`",
+                unmatched.join("`, `"),
+                metadata.workspace_root.display()
+            )
+            .into());
+        }
+        members
+            .iter()
+            .copied()
+            .filter(|package| {
+                let patterns = &cli.workspace.package;
+                patterns
+                    .iter()
+                    .any(|pattern| glob_match(pattern, &package.name))
+            })
+            .collect()
+    } else if cli.workspace.workspace || cli.workspace.all {
+        all_members()
     } else {
-        vec![metadata
-            .packages
+        let current_dir = match &cli.manifest.manifest_path {
+            Some(manifest_path) => path::absolute(manifest_path)?
+                .parent()
+                .map(path::Path::to_path_buf)
+                .unwrap_or_default(),
+            None => env::current_dir()?,
+        };
+        let current_dir = canonicalize(&current_dir);
+
+        // The member with the innermost directory containing the current one, if any
+        let current_package = members
             .iter()
-            .find(|package| package.manifest_path.parent() == Some(&current_dir))
-            .expect("Could not find cargo package in metadata")
-            .to_owned()]
-    })
+            .copied()
+            .filter_map(|package| {
+                let dir = canonicalize(package.manifest_path.parent()?);
+                current_dir
+                    .starts_with(&dir)
+                    .then(|| (dir.components().count(), package))
+            })
+            .max_by_key(|&(depth, _)| depth)
+            .map(|(_, package)| package);
+
+        match current_package {
+            Some(package) if current_dir != canonicalize(&metadata.workspace_root) => {
+                vec![package]
+            }
+            _ => all_members(),
+        }
+    };
+
+    Ok(selected
+        .into_iter()
+        .filter(|package| {
+            let patterns = &cli.workspace.exclude;
+            !patterns
+                .iter()
+                .any(|pattern| glob_match(pattern, &package.name))
+        })
+        .cloned()
+        .collect())
+}
+
+fn canonicalize(path: &path::Path) -> path::PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Whether `name` matches the glob `pattern`, in which `*` matches any sequence of characters and
+/// `?` any single character
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    // Where to resume after the last `*` if the rest doesn't match: the index of the pattern after
+    // it, and of the name it is matched up to
+    let mut star = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
 }
 
 fn cargo_cmd() -> ffi::OsString {
@@ -305,5 +978,88 @@ fn cargo_cmd() -> ffi::OsString {
 #[derive(Eq, PartialEq)]
 pub enum TestOutcome {
     Pass,
-    Fail(process::ExitStatus),
+    Fail(FailStatus),
+    /// Cargo was interrupted with Ctrl-C
+    Interrupted,
+}
+
+/// How a cargo command failed
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FailStatus {
+    /// The exit code, if cargo exited
+    pub code: Option<i32>,
+    /// The signal that terminated cargo, if any
+    pub signal: Option<i32>,
+    /// Whether cargo dumped core when it was terminated
+    pub core_dumped: bool,
+}
+
+impl FailStatus {
+    /// The exit code to exit with for this failure: the exit code of cargo, or 128 plus the signal
+    /// that terminated it, as shells do
+    pub fn exit_code(&self) -> i32 {
+        match (self.code, self.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => 1,
+        }
+    }
+}
+
+impl From<process::ExitStatus> for FailStatus {
+    #[cfg(unix)]
+    fn from(status: process::ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+        FailStatus {
+            code: status.code(),
+            signal: status.signal(),
+            core_dumped: status.core_dumped(),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from(status: process::ExitStatus) -> Self {
+        FailStatus {
+            code: status.code(),
+            signal: None,
+            core_dumped: false,
+        }
+    }
+}
+
+impl fmt::Display for FailStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exit code {code}")?,
+            (None, Some(signal)) => {
+                write!(f, "killed by signal {signal}")?;
+                if let Some(name) = signal_name(signal) {
+                    write!(f, " ({name})")?;
+                }
+            }
+            (None, None) => f.write_str("unknown exit status")?,
+        }
+        if self.core_dumped {
+            f.write_str(", core dumped")?;
+        }
+        Ok(())
+    }
+}
+
+/// The name of the common signals, whose numbers are the same on every Unix
+fn signal_name(signal: i32) -> Option<&'static str> {
+    Some(match signal {
+        1 => "SIGHUP",
+        2 => "SIGINT",
+        3 => "SIGQUIT",
+        4 => "SIGILL",
+        6 => "SIGABRT",
+        8 => "SIGFPE",
+        9 => "SIGKILL",
+        11 => "SIGSEGV",
+        13 => "SIGPIPE",
+        14 => "SIGALRM",
+        15 => "SIGTERM",
+        _ => return None,
+    })
 }
@@ -0,0 +1,58 @@
+//! Delta debugging of failing feature sets, see Zeller and Hildebrandt, "Simplifying and
+//! Isolating Failure-Inducing Input".
+use crate::types::Feature;
+
+/// Reduces `features`, for which `fails` returns `true`, to a subset for which `fails` still
+/// returns `true` but does not for any subset with a single feature removed.
+///
+/// `fails` is called with subsets of `features` and must not be called with `features` itself.
+pub(crate) fn minimize<E>(
+    features: &[Feature],
+    mut fails: impl FnMut(&[Feature]) -> Result<bool, E>,
+) -> Result<Vec<Feature>, E> {
+    if fails(&[])? {
+        // Fails regardless of the features
+        return Ok(vec![]);
+    }
+
+    let mut features = features.to_vec();
+    let mut n = 2;
+    while features.len() >= 2 {
+        let chunks: Vec<Vec<Feature>> = (0..n)
+            .map(|i| features[i * features.len() / n..(i + 1) * features.len() / n].to_vec())
+            .collect();
+
+        let mut reduced = None;
+        for chunk in &chunks {
+            if fails(chunk)? {
+                reduced = Some((chunk.clone(), 2));
+                break;
+            }
+        }
+        if reduced.is_none() && n > 2 {
+            for i in 0..n {
+                let complement: Vec<Feature> = chunks
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .flat_map(|(_, chunk)| chunk.iter().cloned())
+                    .collect();
+                if fails(&complement)? {
+                    reduced = Some((complement, n - 1));
+                    break;
+                }
+            }
+        }
+
+        match reduced {
+            Some((subset, next_n)) => {
+                features = subset;
+                n = next_n.min(features.len()).max(2);
+            }
+            None if n >= features.len() => break,
+            None => n = (n * 2).min(features.len()),
+        }
+    }
+
+    Ok(features)
+}
@@ -0,0 +1,281 @@
+use crate::{
+    test_runner::lock_output, types::FeatureList, Combination, CombinationRun, MessageFormat,
+    TestOutcome,
+};
+use std::io::Write;
+use termcolor::WriteColor;
+
+/// Prints a table with the outcome of every combination, including those that were interrupted
+/// or never started
+pub(crate) fn print_summary(combinations: &[Combination], runs: &[Option<CombinationRun>]) {
+    let rows: Vec<_> = combinations
+        .iter()
+        .zip(runs)
+        .map(|(combination, run)| {
+            (
+                run.as_ref().map(|run| &run.outcome),
+                combination.package.name.as_str(),
+                format!("[{}]", combination.feature_set),
+            )
+        })
+        .collect();
+
+    let count = |predicate: fn(Option<&TestOutcome>) -> bool| {
+        rows.iter()
+            .filter(|(outcome, _, _)| predicate(*outcome))
+            .count()
+    };
+    let passed = count(|outcome| outcome == Some(&TestOutcome::Pass));
+    let failed = count(|outcome| matches!(outcome, Some(TestOutcome::Fail(_))));
+    let interrupted = count(|outcome| outcome == Some(&TestOutcome::Interrupted));
+    let not_started = count(|outcome| outcome.is_none());
+    let result = |outcome: Option<&TestOutcome>| match outcome {
+        Some(TestOutcome::Pass) => ("pass", termcolor::Color::Green),
+        Some(TestOutcome::Fail(_)) => ("FAIL", termcolor::Color::Red),
+        Some(TestOutcome::Interrupted) => ("interrupted", termcolor::Color::Yellow),
+        None => ("not started", termcolor::Color::Yellow),
+    };
+    let result_width = rows
+        .iter()
+        .map(|(outcome, _, _)| result(*outcome).0.len())
+        .chain(Some("Result".len()))
+        .max()
+        .unwrap_or_default();
+    let package_width = rows
+        .iter()
+        .map(|(_, package, _)| package.len())
+        .chain(Some("Package".len()))
+        .max()
+        .unwrap_or_default();
+
+    let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
+    println!();
+    println!(
+        "{:<result_width$}  {:<package_width$}  Features",
+        "Result", "Package"
+    );
+    for (outcome, package, features) in &rows {
+        let (result, color) = result(*outcome);
+        stdout
+            .set_color(
+                termcolor::ColorSpec::new()
+                    .set_fg(Some(color))
+                    .set_bold(true),
+            )
+            .unwrap();
+        write!(stdout, "{result:<result_width$}").unwrap();
+        stdout.reset().unwrap();
+        match outcome {
+            Some(TestOutcome::Fail(status)) if status.signal.is_some() => {
+                println!("  {package:<package_width$}  {features}  ({status})")
+            }
+            _ => println!("  {package:<package_width$}  {features}"),
+        }
+    }
+    println!();
+    if interrupted + not_started > 0 {
+        println!(
+            "{passed} passed; {failed} failed; {interrupted} interrupted; {not_started} not started"
+        );
+    } else {
+        println!("{passed} passed; {failed} failed");
+    }
+}
+
+/// Reports how many combinations `--resume` skips because they passed before
+pub(crate) fn print_resumed(skipped: usize, message_format: MessageFormat) {
+    print_note(
+        "Resuming",
+        &format!(
+            "skipping {} that passed before",
+            feature_combinations(skipped)
+        ),
+        message_format,
+    );
+}
+
+/// "1 feature combination" or "`count` feature combinations"
+fn feature_combinations(count: usize) -> String {
+    match count {
+        1 => "1 feature combination".to_owned(),
+        count => format!("{count} feature combinations"),
+    }
+}
+
+/// Reports how many combinations `--failed` reruns because they failed before
+pub(crate) fn print_rerunning_failed(count: usize, message_format: MessageFormat) {
+    print_note(
+        "Rerunning",
+        &format!("{} that failed before", feature_combinations(count)),
+        message_format,
+    );
+}
+
+fn print_note(status: &str, note: &str, message_format: MessageFormat) {
+    if message_format != MessageFormat::Human {
+        return;
+    }
+    let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
+    stdout
+        .set_color(
+            termcolor::ColorSpec::new()
+                .set_fg(Some(termcolor::Color::Cyan))
+                .set_bold(true),
+        )
+        .unwrap();
+    write!(stdout, "{status:>12} ").unwrap();
+    stdout.reset().unwrap();
+    println!("{note}");
+}
+
+/// Prints a warning about something that doesn't stop the run
+pub(crate) fn print_warning(warning: &str) {
+    let mut stderr = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
+    stderr
+        .set_color(
+            termcolor::ColorSpec::new()
+                .set_fg(Some(termcolor::Color::Yellow))
+                .set_bold(true),
+        )
+        .unwrap();
+    write!(stderr, "warning").unwrap();
+    stderr.reset().unwrap();
+    eprintln!(": {warning}");
+}
+
+/// Reports a combination that isn't run because it passed before, according to the cache
+pub(crate) fn print_cached(
+    combination: &Combination,
+    command: &str,
+    message_format: MessageFormat,
+) {
+    if message_format != MessageFormat::Human {
+        return;
+    }
+    let _lock = lock_output();
+    let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
+    stdout
+        .set_color(
+            termcolor::ColorSpec::new()
+                .set_fg(Some(termcolor::Color::Green))
+                .set_bold(true),
+        )
+        .unwrap();
+    write!(stdout, "{:>12} {command} ", "Cached").unwrap();
+    stdout.reset().unwrap();
+    println!(
+        "crate={} features=[{}]",
+        combination.package.name, combination.feature_set
+    );
+}
+
+/// Reports the minimal failing feature set a failing combination was reduced to, as a
+/// `minimized` message in JSON
+pub(crate) fn print_minimized(
+    combination: &Combination,
+    minimal: &FeatureList,
+    message_format: MessageFormat,
+) {
+    match message_format {
+        MessageFormat::Human => {
+            let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
+            stdout
+                .set_color(
+                    termcolor::ColorSpec::new()
+                        .set_fg(Some(termcolor::Color::Yellow))
+                        .set_bold(true),
+                )
+                .unwrap();
+            write!(stdout, "{:>12} ", "Minimized").unwrap();
+            stdout.reset().unwrap();
+            println!(
+                "crate={} features=[{}] to features=[{}]",
+                combination.package.name, combination.feature_set, minimal
+            );
+        }
+        MessageFormat::Json => {
+            let mut message = combination_to_json("minimized", combination);
+            message["minimal_features"] = minimal.iter().map(|f| &**f).collect::<Vec<_>>().into();
+            print_json(message);
+        }
+    }
+}
+
+/// Emits the `feature-matrix` message listing every combination that is going to be run
+pub(crate) fn print_json_matrix(combinations: &[Combination]) {
+    let combinations: Vec<_> = combinations
+        .iter()
+        .map(|combination| {
+            let mut combination = combination_to_json("", combination);
+            combination.remove("reason");
+            combination
+        })
+        .collect();
+    print_json(json::object! {
+        reason: "feature-matrix",
+        combinations: combinations,
+    });
+}
+
+/// Emits the `combination-started` message
+pub(crate) fn print_json_started(combination: &Combination) {
+    print_json(combination_to_json("combination-started", combination));
+}
+
+/// Emits the `combination-finished` message
+pub(crate) fn print_json_finished(combination: &Combination, run: &CombinationRun) {
+    let mut message = combination_to_json("combination-finished", combination);
+    message["success"] = (run.outcome == TestOutcome::Pass).into();
+    message["exit_code"] = match &run.outcome {
+        TestOutcome::Pass => 0.into(),
+        TestOutcome::Fail(status) => status.code.into(),
+        TestOutcome::Interrupted => json::Null,
+    };
+    if run.outcome == TestOutcome::Interrupted {
+        message["interrupted"] = true.into();
+    }
+    if run.cached {
+        message["cached"] = true.into();
+    }
+    if let TestOutcome::Fail(status) = &run.outcome {
+        message["signal"] = status.signal.into();
+        message["core_dumped"] = status.core_dumped.into();
+    }
+    message["duration_secs"] = run.duration.as_secs_f64().into();
+    print_json(message);
+}
+
+/// Emits the `summary` message, counting combinations that passed, failed, were interrupted or
+/// were not run
+pub(crate) fn print_json_summary(runs: &[Option<CombinationRun>]) {
+    let count = |outcome: &TestOutcome| {
+        runs.iter()
+            .flatten()
+            .filter(|run| run.outcome == *outcome)
+            .count()
+    };
+    let passed = count(&TestOutcome::Pass);
+    let interrupted = count(&TestOutcome::Interrupted);
+    let not_run = runs.iter().filter(|run| run.is_none()).count();
+    print_json(json::object! {
+        reason: "summary",
+        passed: passed,
+        failed: runs.len() - passed - interrupted - not_run,
+        interrupted: interrupted,
+        not_run: not_run,
+    });
+}
+
+fn combination_to_json(reason: &str, combination: &Combination) -> json::JsonValue {
+    let features: Vec<&str> = combination.feature_set.iter().map(|f| &**f).collect();
+    json::object! {
+        reason: reason,
+        package: combination.package.name.as_str(),
+        features: features,
+    }
+}
+
+fn print_json(message: json::JsonValue) {
+    let _lock = lock_output();
+    println!("{}", message.dump());
+}
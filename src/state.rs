@@ -0,0 +1,175 @@
+//! The state file under the target directory, which records the feature combinations that passed
+//! and failed, so that `--resume` can skip the former and `--failed` can rerun the latter.
+use crate::{json_file, report, Combination};
+use std::{collections::BTreeSet, error, io, path};
+
+/// A feature combination of a package that was run with a cargo command and its arguments. The
+/// target directory may be shared between workspaces, which are told apart by their root.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Entry {
+    workspace: String,
+    package: String,
+    features: Vec<String>,
+    command: String,
+    args: Vec<String>,
+}
+
+impl Entry {
+    fn to_json(&self) -> json::JsonValue {
+        json::object! {
+            workspace: self.workspace.as_str(),
+            package: self.package.as_str(),
+            features: self.features.clone(),
+            command: self.command.as_str(),
+            args: self.args.clone(),
+        }
+    }
+
+    fn from_json(json: &json::JsonValue) -> Option<Self> {
+        let strings = |json: &json::JsonValue| -> Option<Vec<String>> {
+            if !json.is_array() {
+                return None;
+            }
+            json.members()
+                .map(|member| member.as_str().map(str::to_owned))
+                .collect()
+        };
+        Some(Entry {
+            workspace: json["workspace"].as_str()?.to_owned(),
+            package: json["package"].as_str()?.to_owned(),
+            features: strings(&json["features"])?,
+            command: json["command"].as_str()?.to_owned(),
+            args: strings(&json["args"])?,
+        })
+    }
+}
+
+/// The combinations that passed and failed, as recorded in the state file
+pub(crate) struct State {
+    path: path::PathBuf,
+    workspace: String,
+    command: String,
+    args: Vec<String>,
+    passed: BTreeSet<Entry>,
+    failed: BTreeSet<Entry>,
+}
+
+impl State {
+    /// Reads the state file in `target_directory`, if there is one, for running `command` with
+    /// `args` in the workspace at `workspace_root`. The state only saves work, so a file that
+    /// can't be read, e.g. because an older version wrote it, is replaced after a warning.
+    pub(crate) fn load(
+        target_directory: &path::Path,
+        workspace_root: &path::Path,
+        command: &str,
+        args: &[String],
+    ) -> Self {
+        let path = target_directory
+            .join("cargo-all-features")
+            .join("state.json");
+        let (passed, failed) = read(&path).unwrap_or_else(|err| {
+            report::print_warning(&format!("{err}, starting from an empty state"));
+            Default::default()
+        });
+        State {
+            path,
+            workspace: workspace_root.to_string_lossy().into_owned(),
+            command: command.to_owned(),
+            args: args.to_vec(),
+            passed,
+            failed,
+        }
+    }
+
+    /// Whether `combination` passed in the workspace with the same cargo command and arguments
+    pub(crate) fn passed(&self, combination: &Combination) -> bool {
+        self.passed.contains(&self.entry(combination))
+    }
+
+    /// Whether `combination` failed the last time it was run with the same cargo command and
+    /// arguments
+    pub(crate) fn failed(&self, combination: &Combination) -> bool {
+        self.failed.contains(&self.entry(combination))
+    }
+
+    /// Forgets the combinations that were run in the workspace with the cargo command and
+    /// arguments, so that a later `--resume` or `--failed` continues from this run instead of an
+    /// earlier one
+    pub(crate) fn clear(&mut self) -> io::Result<()> {
+        let (workspace, command, args) = (
+            self.workspace.clone(),
+            self.command.clone(),
+            self.args.clone(),
+        );
+        let other_run = move |entry: &Entry| {
+            entry.workspace != workspace || entry.command != command || entry.args != args
+        };
+        self.update(|passed, failed| {
+            passed.retain(&other_run);
+            failed.retain(&other_run);
+        })
+    }
+
+    /// Records that `combination` passed
+    pub(crate) fn record_pass(&mut self, combination: &Combination) -> io::Result<()> {
+        let entry = self.entry(combination);
+        self.update(|passed, failed| {
+            failed.remove(&entry);
+            passed.insert(entry);
+        })
+    }
+
+    /// Records that `combination` failed
+    pub(crate) fn record_failure(&mut self, combination: &Combination) -> io::Result<()> {
+        let entry = self.entry(combination);
+        self.update(|passed, failed| {
+            passed.remove(&entry);
+            failed.insert(entry);
+        })
+    }
+
+    fn entry(&self, combination: &Combination) -> Entry {
+        Entry {
+            workspace: self.workspace.clone(),
+            package: combination.package.name.clone(),
+            features: combination
+                .feature_set
+                .iter()
+                .map(|feature| feature.to_string())
+                .collect(),
+            command: self.command.clone(),
+            args: self.args.clone(),
+        }
+    }
+
+    /// Applies `change` to the passed and failed combinations in the state file. Other runs may
+    /// share the target directory, so the file is read again under a lock instead of overwritten
+    /// with the combinations read by `load`.
+    fn update(
+        &mut self,
+        change: impl FnOnce(&mut BTreeSet<Entry>, &mut BTreeSet<Entry>),
+    ) -> io::Result<()> {
+        let _lock = json_file::lock(&self.path)?;
+        // A file that can't be read was already reported by `load`
+        let (mut passed, mut failed) = read(&self.path).unwrap_or_default();
+        change(&mut passed, &mut failed);
+        let json = json::object! {
+            passed: passed.iter().map(Entry::to_json).collect::<Vec<_>>(),
+            failed: failed.iter().map(Entry::to_json).collect::<Vec<_>>(),
+        };
+        json_file::write(&self.path, json)?;
+        self.passed = passed;
+        self.failed = failed;
+        Ok(())
+    }
+}
+
+/// Reads the passed and failed combinations from the state file at `path`
+fn read(path: &path::Path) -> Result<(BTreeSet<Entry>, BTreeSet<Entry>), Box<dyn error::Error>> {
+    json_file::read(path, |json| {
+        let entries = |key: &str| -> Option<BTreeSet<Entry>> {
+            json[key].members().map(Entry::from_json).collect()
+        };
+        Some((entries("passed")?, entries("failed")?))
+    })
+}
@@ -1,7 +1,24 @@
-use crate::types::FeatureList;
-use std::{error, path, process};
+use crate::{
+    interrupt::{self, RunningChild},
+    types::FeatureList,
+    MessageFormat,
+};
+use std::{
+    env, error,
+    io::{self, IsTerminal, Write},
+    path, process, sync, thread,
+};
 use termcolor::WriteColor;
 
+/// Serializes printing between runners executing concurrently
+static OUTPUT_LOCK: sync::Mutex<()> = sync::Mutex::new(());
+
+pub(crate) fn lock_output() -> sync::MutexGuard<'static, ()> {
+    OUTPUT_LOCK
+        .lock()
+        .unwrap_or_else(sync::PoisonError::into_inner)
+}
+
 pub struct TestRunner {
     command: process::Command,
     crate_name: String,
@@ -9,6 +26,22 @@ pub struct TestRunner {
     features: String,
     working_dir: path::PathBuf,
     cargo_command: String,
+    output_mode: OutputMode,
+    message_format: MessageFormat,
+    captured_stdout: Vec<u8>,
+    captured_stderr: Vec<u8>,
+}
+
+/// What happens to the output of cargo
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputMode {
+    /// cargo writes to the terminal directly
+    Inherit,
+    /// The output is written through as it comes, and captured
+    Tee,
+    /// The output is captured and printed in one piece once cargo exits, so that the output of
+    /// runners executing concurrently does not interleave
+    Buffer,
 }
 
 fn split_slice<'a>(slice: &'a [String], item: &'a str) -> (&'a [String], &'a [String]) {
@@ -19,6 +52,23 @@ fn split_slice<'a>(slice: &'a [String], item: &'a str) -> (&'a [String], &'a [St
     }
 }
 
+/// Copies everything from `reader` to `writer`, returning a copy of it
+fn tee(mut reader: impl io::Read, mut writer: impl io::Write) -> io::Result<Vec<u8>> {
+    let mut captured = Vec::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(captured),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        writer.write_all(&buffer[..n])?;
+        writer.flush()?;
+        captured.extend_from_slice(&buffer[..n]);
+    }
+}
+
 impl TestRunner {
     pub fn new(
         cargo_command: String,
@@ -63,33 +113,160 @@ impl TestRunner {
             features,
             working_dir,
             cargo_command,
+            output_mode: OutputMode::Inherit,
+            message_format: MessageFormat::Human,
+            captured_stdout: Vec::new(),
+            captured_stderr: Vec::new(),
         }
     }
 
-    pub fn run(&mut self) -> Result<crate::TestOutcome, Box<dyn error::Error>> {
+    /// Build into `target_dir` instead of the default target directory, so that runners
+    /// executing concurrently do not block on each other's build directory lock
+    pub fn target_dir(&mut self, target_dir: &path::Path) -> &mut Self {
+        self.command.env("CARGO_TARGET_DIR", target_dir);
+        self
+    }
+
+    pub fn output_mode(&mut self, output_mode: OutputMode) -> &mut Self {
+        self.output_mode = output_mode;
+        self
+    }
+
+    /// With [`MessageFormat::Json`], stdout is reserved for JSON messages: no status lines are
+    /// printed and the stdout of cargo is redirected to stderr
+    pub fn message_format(&mut self, message_format: MessageFormat) -> &mut Self {
+        self.message_format = message_format;
+        self
+    }
+
+    /// The stdout of cargo, captured by the last [`TestRunner::run`] unless the output mode is
+    /// [`OutputMode::Inherit`]
+    pub fn captured_stdout(&self) -> &[u8] {
+        &self.captured_stdout
+    }
+
+    /// The stderr of cargo, captured by the last [`TestRunner::run`] unless the output mode is
+    /// [`OutputMode::Inherit`]
+    pub fn captured_stderr(&self) -> &[u8] {
+        &self.captured_stderr
+    }
+
+    pub fn run(&mut self) -> Result<crate::TestOutcome, Box<dyn error::Error + Send + Sync>> {
+        let human = self.message_format == MessageFormat::Human;
+        if human {
+            let _lock = lock_output();
+            self.print_status("Running", termcolor::Color::Cyan);
+        }
+
+        self.command.current_dir(&self.working_dir);
+
+        let status = match self.output_mode {
+            OutputMode::Inherit => {
+                let mut child = self
+                    .command
+                    .stdout(if human {
+                        process::Stdio::inherit()
+                    } else {
+                        io::stderr().into()
+                    })
+                    .stderr(process::Stdio::inherit())
+                    .spawn()?;
+                let _running = RunningChild::new(&child);
+                child.wait()?
+            }
+            OutputMode::Tee => {
+                let mut child = self
+                    .command
+                    .stdout(process::Stdio::piped())
+                    .stderr(process::Stdio::piped())
+                    .spawn()?;
+                let _running = RunningChild::new(&child);
+                let child_stdout = child.stdout.take().expect("stdout is piped");
+                let child_stderr = child.stderr.take().expect("stderr is piped");
+
+                let (stdout, stderr) = thread::scope(|scope| {
+                    let stderr = scope.spawn(|| tee(child_stderr, io::stderr()));
+                    let stdout = if human {
+                        tee(child_stdout, io::stdout())
+                    } else {
+                        tee(child_stdout, io::stderr())
+                    };
+                    (stdout, stderr.join().expect("stderr reader panicked"))
+                });
+                self.captured_stdout = stdout?;
+                self.captured_stderr = stderr?;
+
+                child.wait()?
+            }
+            OutputMode::Buffer => {
+                // cargo disables colors when writing to a pipe
+                if io::stderr().is_terminal() && env::var_os("CARGO_TERM_COLOR").is_none() {
+                    self.command.env("CARGO_TERM_COLOR", "always");
+                }
+
+                let child = self
+                    .command
+                    .stdout(process::Stdio::piped())
+                    .stderr(process::Stdio::piped())
+                    .spawn()?;
+                let running = RunningChild::new(&child);
+                let output = child.wait_with_output()?;
+                drop(running);
+
+                let _lock = lock_output();
+                if human && output.status.success() {
+                    self.print_status("Finished", termcolor::Color::Green);
+                } else if human && interrupt::interrupted_process(output.status) {
+                    self.print_status("Interrupted", termcolor::Color::Yellow);
+                } else if human {
+                    self.print_status("Failed", termcolor::Color::Red);
+                }
+                io::stderr().write_all(&output.stderr)?;
+                if human {
+                    io::stdout().write_all(&output.stdout)?;
+                } else {
+                    io::stderr().write_all(&output.stdout)?;
+                }
+
+                self.captured_stdout = output.stdout;
+                self.captured_stderr = output.stderr;
+                output.status
+            }
+        };
+
+        if status.success() {
+            return Ok(crate::TestOutcome::Pass);
+        }
+        if interrupt::interrupted_process(status) {
+            return Ok(crate::TestOutcome::Interrupted);
+        }
+
+        let status = crate::FailStatus::from(status);
+        if human && status.signal.is_some() {
+            let _lock = lock_output();
+            self.print_status_with_detail("Killed", termcolor::Color::Red, &format!(" ({status})"));
+        }
+        Ok(crate::TestOutcome::Fail(status))
+    }
+
+    fn print_status(&self, status: &str, color: termcolor::Color) {
+        self.print_status_with_detail(status, color, "");
+    }
+
+    fn print_status_with_detail(&self, status: &str, color: termcolor::Color, detail: &str) {
         let mut stdout = termcolor::StandardStream::stdout(termcolor::ColorChoice::Auto);
         stdout
             .set_color(
                 termcolor::ColorSpec::new()
-                    .set_fg(Some(termcolor::Color::Cyan))
+                    .set_fg(Some(color))
                     .set_bold(true),
             )
             .unwrap();
-        print!("     Running {} ", self.cargo_command);
+        print!("{:>12} {} ", status, self.cargo_command);
         stdout.reset().unwrap();
-        println!("crate={} features=[{}]", self.crate_name, self.features);
-
-        let output = self
-            .command
-            .stdout(process::Stdio::inherit())
-            .stderr(process::Stdio::inherit())
-            .current_dir(&self.working_dir)
-            .output()?;
-
-        Ok(if output.status.success() {
-            crate::TestOutcome::Pass
-        } else {
-            crate::TestOutcome::Fail(output.status)
-        })
+        println!(
+            "crate={} features=[{}]{detail}",
+            self.crate_name, self.features
+        );
     }
 }
@@ -2,6 +2,7 @@
 /// transparent wrappers around [`String`] and [`Vec<String>`].
 use std::{
     convert::{AsMut, AsRef},
+    fmt,
     iter::FromIterator,
     ops::{Deref, DerefMut},
 };
@@ -20,6 +21,19 @@ impl FromIterator<Feature> for FeatureList {
     }
 }
 
+/// Formats the features as a comma separated list, the way cargo's `--features` expects them
+impl fmt::Display for FeatureList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, feature) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(feature)?;
+        }
+        Ok(())
+    }
+}
+
 impl AsMut<<FeatureList as Deref>::Target> for &mut FeatureList {
     fn as_mut(&mut self) -> &mut <FeatureList as Deref>::Target {
         self
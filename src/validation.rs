@@ -0,0 +1,269 @@
+//! Validation of the `cargo-all-features` metadata tables, so that misspelled keys and values of
+//! the wrong type are reported instead of being ignored.
+use crate::cargo_metadata::Error;
+use crate::constraints::Expr;
+use crate::report;
+use json::JsonValue;
+
+/// The type of the value of an option
+#[derive(Clone, Copy)]
+enum Type {
+    Bool,
+    Integer,
+    String,
+    Strings,
+    FeatureSets,
+    FeatureRequirements,
+}
+
+impl Type {
+    fn matches(self, value: &JsonValue) -> bool {
+        fn is_strings(value: &JsonValue) -> bool {
+            value.is_array() && value.members().all(JsonValue::is_string)
+        }
+
+        match self {
+            Type::Bool => value.is_boolean(),
+            Type::Integer => value.as_usize().is_some(),
+            Type::String => value.is_string(),
+            Type::Strings => is_strings(value),
+            Type::FeatureSets => value.is_array() && value.members().all(is_strings),
+            Type::FeatureRequirements => {
+                value.is_object() && value.entries().all(|(_, value)| is_strings(value))
+            }
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Type::Bool => "a boolean",
+            Type::Integer => "a non-negative integer",
+            Type::String => "a string",
+            Type::Strings => "an array of strings",
+            Type::FeatureSets => "an array of arrays of strings",
+            Type::FeatureRequirements => "a table of arrays of strings",
+        }
+    }
+}
+
+const OPTIONS: &[(&str, Type)] = &[
+    ("skip_package", Type::Bool),
+    ("skip_feature_sets", Type::FeatureSets),
+    ("mutually_exclusive_features", Type::FeatureSets),
+    ("require_one_of", Type::FeatureSets),
+    ("feature_requires", Type::FeatureRequirements),
+    ("constraints", Type::Strings),
+    ("skip_optional_dependencies", Type::Bool),
+    ("extra_features", Type::Strings),
+    ("denylist", Type::Strings),
+    ("always_include_features", Type::Strings),
+    ("max_combination_size", Type::Integer),
+    ("allowlist", Type::Strings),
+    ("strategy", Type::String),
+];
+
+/// The cargo commands that come with cargo or rustup, for which a sub-table is expected
+const CARGO_COMMANDS: &[&str] = &[
+    "bench", "build", "check", "clippy", "doc", "fix", "miri", "run", "rustc", "rustdoc", "test",
+];
+
+/// Checks the keys and the types of the values of `config`, the options at `path` of the
+/// `package`, or of the workspace if there is none, including its sub-tables for cargo commands
+/// and profiles. Warns about sub-tables for cargo commands other than the built-in ones and the
+/// `command` being run, which are likely misspelled.
+pub(crate) fn validate(
+    package: Option<&str>,
+    path: &str,
+    config: &JsonValue,
+    command: Option<&str>,
+) -> Result<(), Error> {
+    if config.is_null() {
+        return Ok(());
+    }
+    if !config.is_object() {
+        return Err(invalid_type(package, path.to_owned(), "a table"));
+    }
+    validate_options(package, path, config, true, command)
+}
+
+fn invalid_type(package: Option<&str>, path: String, expected: &'static str) -> Error {
+    Error::InvalidType {
+        package: package.map(str::to_owned),
+        path,
+        expected,
+    }
+}
+
+fn validate_options(
+    package: Option<&str>,
+    path: &str,
+    config: &JsonValue,
+    top_level: bool,
+    command: Option<&str>,
+) -> Result<(), Error> {
+    for (key, value) in config.entries() {
+        let key_path = format!("{path}.{key}");
+        if let Some(&(_, option_type)) = OPTIONS.iter().find(|(option, _)| *option == key) {
+            if !option_type.matches(value) {
+                return Err(invalid_type(package, key_path, option_type.description()));
+            }
+        } else if top_level && key == "profiles" {
+            if !value.is_object() {
+                return Err(invalid_type(package, key_path, "a table"));
+            }
+            for (profile, options) in value.entries() {
+                let profile_path = format!("{key_path}.{profile}");
+                if !options.is_object() {
+                    return Err(invalid_type(package, profile_path, "a table"));
+                }
+                validate_options(package, &profile_path, options, false, command)?;
+            }
+        } else if top_level && value.is_object() && suggestion(key, top_level).is_none() {
+            // The options for a cargo command
+            validate_options(package, &key_path, value, false, command)?;
+            if !CARGO_COMMANDS.contains(&key) && command != Some(key) {
+                warn_unknown_command(package, &key_path, key, command);
+            }
+        } else {
+            return Err(Error::UnknownKey {
+                package: package.map(str::to_owned),
+                path: key_path,
+                suggestion: suggestion(key, top_level),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Warns that the sub-table `key` at `path` is for a cargo command that is neither built in nor
+/// the `command` being run
+fn warn_unknown_command(package: Option<&str>, path: &str, key: &str, command: Option<&str>) {
+    let (subject, root) = match package {
+        Some(package) => (format!("Package {package}"), "package"),
+        None => ("Workspace".to_owned(), "workspace"),
+    };
+    let mut warning =
+        format!("{subject} has options for unknown cargo command `{key}` in `[{root}.{path}]`");
+    // Commands are short, so allow for two typos, e.g. swapped letters
+    let nearest = CARGO_COMMANDS
+        .iter()
+        .copied()
+        .chain(command)
+        .map(|command| (edit_distance(key, command), command))
+        .filter(|&(distance, _)| distance <= 2)
+        .min();
+    if let Some((_, command)) = nearest {
+        warning.push_str(&format!(", did you mean `{command}`?"));
+    }
+    report::print_warning(&warning);
+}
+
+/// The known key closest to the unknown `key`, if any is close enough to be a typo
+fn suggestion(key: &str, top_level: bool) -> Option<&'static str> {
+    let profiles = top_level.then_some("profiles");
+    OPTIONS
+        .iter()
+        .map(|&(option, _)| option)
+        .chain(profiles)
+        .map(|option| (edit_distance(key, option), option))
+        .filter(|&(distance, _)| distance <= (key.chars().count() / 3).max(1))
+        .min()
+        .map(|(_, option)| option)
+}
+
+/// The Levenshtein distance between `a` and `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Checks that every feature named in `config`, the options at `path` of the `package`, and in
+/// its sub-tables is one for which `is_known` returns `true`.
+///
+/// `config` must have been validated with [`validate`] first.
+pub(crate) fn validate_feature_names(
+    package: &str,
+    path: &str,
+    config: &JsonValue,
+    is_known: &impl Fn(&str) -> bool,
+) -> Result<(), Error> {
+    for (key, value) in config.entries() {
+        let key_path = format!("{path}.{key}");
+        let features: Vec<&str> = match key {
+            "denylist" | "allowlist" | "extra_features" | "always_include_features" => {
+                value.members().filter_map(JsonValue::as_str).collect()
+            }
+            "skip_feature_sets" | "mutually_exclusive_features" | "require_one_of" => value
+                .members()
+                .flat_map(JsonValue::members)
+                .filter_map(JsonValue::as_str)
+                .collect(),
+            "feature_requires" => value
+                .entries()
+                .flat_map(|(feature, requirements)| {
+                    std::iter::once(feature)
+                        .chain(requirements.members().filter_map(JsonValue::as_str))
+                })
+                .collect(),
+            "constraints" => {
+                // Invalid constraints are reported when parsing them
+                let constraints: Vec<Expr> = value
+                    .members()
+                    .filter_map(JsonValue::as_str)
+                    .filter_map(|constraint| Expr::parse(constraint).ok())
+                    .collect();
+                let features: Vec<String> = constraints
+                    .iter()
+                    .flat_map(Expr::features)
+                    .map(str::to_owned)
+                    .collect();
+                check_features(
+                    package,
+                    key_path,
+                    features.iter().map(String::as_str),
+                    is_known,
+                )?;
+                continue;
+            }
+            "profiles" => {
+                for (profile, options) in value.entries() {
+                    let profile_path = format!("{key_path}.{profile}");
+                    validate_feature_names(package, &profile_path, options, is_known)?;
+                }
+                continue;
+            }
+            _ if value.is_object() => {
+                validate_feature_names(package, &key_path, value, is_known)?;
+                continue;
+            }
+            _ => continue,
+        };
+        check_features(package, key_path, features.into_iter(), is_known)?;
+    }
+    Ok(())
+}
+
+fn check_features<'a>(
+    package: &str,
+    path: String,
+    mut features: impl Iterator<Item = &'a str>,
+    is_known: &impl Fn(&str) -> bool,
+) -> Result<(), Error> {
+    match features.find(|feature| !is_known(feature)) {
+        Some(feature) => Err(Error::UnknownFeature {
+            package: package.to_owned(),
+            path,
+            feature: feature.to_owned(),
+        }),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,98 @@
+use cargo_all_features::cargo_metadata::{Error, Metadata, Package};
+use std::convert::TryFrom;
+
+fn package(dependencies: json::JsonValue, config: json::JsonValue) -> json::JsonValue {
+    json::object! {
+        id: "foo 0.1.0",
+        name: "foo",
+        manifest_path: "/workspace/Cargo.toml",
+        dependencies: dependencies,
+        features: { A: [], B: ["A"] },
+        metadata: { "cargo-all-features": config },
+    }
+}
+
+fn metadata(package: json::JsonValue) -> json::JsonValue {
+    json::object! {
+        workspace_root: "/workspace",
+        target_directory: "/workspace/target",
+        workspace_members: ["foo 0.1.0"],
+        packages: [package],
+    }
+}
+
+#[test]
+fn valid() {
+    let dependencies = json::array![{ name: "bar", rename: null, optional: true }];
+    let config = json::object! { denylist: ["B"] };
+    let metadata = Metadata::try_from(metadata(package(dependencies, config))).unwrap();
+    assert_eq!(metadata.packages[0].name, "foo");
+    assert!(metadata.packages[0].dependencies[0].optional);
+    assert_eq!(metadata.packages[0].denylist.len(), 1);
+}
+
+#[test]
+fn missing_field() {
+    let dependencies = json::array![{ name: "bar" }];
+    let err = Metadata::try_from(metadata(package(dependencies, json::Null))).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::MissingField { package: Some(package), path }
+            if package == "foo" && path == "dependencies[0].optional"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Package foo in the output of `cargo metadata` has no `dependencies[0].optional`"
+    );
+}
+
+#[test]
+fn missing_workspace_field() {
+    let mut json = metadata(package(json::array![], json::Null));
+    json.remove("target_directory");
+    let err = Metadata::try_from(json).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::MissingField { package: None, path } if path == "target_directory"
+    ));
+}
+
+#[test]
+fn invalid_type() {
+    let config = json::object! { denylist: ["A", 1] };
+    let err = Package::try_from(package(json::array![], config)).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::InvalidType { package: Some(package), path, expected: "a string" }
+            if package == "foo" && path == "metadata.cargo-all-features.denylist[1]"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Package foo has `denylist[1]` of the wrong type in `[package.metadata.cargo-all-features]`, expected a string"
+    );
+}
+
+#[test]
+fn invalid_type_in_output() {
+    let dependencies = json::array![{ name: "bar", optional: "yes" }];
+    let err = Package::try_from(package(dependencies, json::Null)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Package foo in the output of `cargo metadata` has `dependencies[0].optional` of the wrong type, expected a boolean"
+    );
+}
+
+#[test]
+fn conflicting_options() {
+    let config = json::object! { allowlist: ["A"], denylist: ["B"] };
+    let err = Metadata::try_from(metadata(package(json::array![], config))).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::ConflictingOptions { package, path, .. }
+            if package == "foo" && path == "metadata.cargo-all-features.allowlist"
+    ));
+    assert_eq!(
+        err.to_string(),
+        "Package foo has both `allowlist` and `denylist` keys"
+    );
+}
@@ -0,0 +1,71 @@
+use cargo_all_features::constraints::Expr;
+
+fn feature(name: &str) -> Box<Expr> {
+    Box::new(Expr::Feature(name.to_owned()))
+}
+
+#[test]
+fn precedence() {
+    assert_eq!(
+        Expr::parse("!a && b || c -> d").unwrap(),
+        Expr::Implies(
+            Box::new(Expr::Or(
+                Box::new(Expr::And(Box::new(Expr::Not(feature("a"))), feature("b"))),
+                feature("c"),
+            )),
+            feature("d"),
+        )
+    );
+}
+
+#[test]
+fn implies_is_right_associative() {
+    assert_eq!(
+        Expr::parse("a -> b -> c").unwrap(),
+        Expr::Implies(
+            feature("a"),
+            Box::new(Expr::Implies(feature("b"), feature("c")))
+        )
+    );
+}
+
+#[test]
+fn feature_names() {
+    assert_eq!(
+        Expr::parse("(tls-rustls<->dep:rustls)&&serde/derive").unwrap(),
+        Expr::And(
+            Box::new(Expr::Iff(feature("tls-rustls"), feature("dep:rustls"))),
+            feature("serde/derive"),
+        )
+    );
+    assert_eq!(
+        Expr::parse("a->b").unwrap(),
+        Expr::Implies(feature("a"), feature("b"))
+    );
+}
+
+#[test]
+fn eval() {
+    let expr = Expr::parse("baz -> (qux || quux)").unwrap();
+    assert!(expr.eval(&|f| f == "qux" || f == "baz"));
+    assert!(expr.eval(&|_| false));
+    assert!(!expr.eval(&|f| f == "baz"));
+}
+
+#[test]
+fn errors() {
+    for (source, offset, message) in [
+        ("a &&", 4, "expected a feature or `(`, found the end"),
+        ("(a || b", 7, "expected `)`"),
+        ("a b", 2, "expected an operator, found feature `b`"),
+        ("a & b", 2, "unknown operator"),
+        ("a = b", 2, "unexpected character `=`"),
+    ] {
+        let err = Expr::parse(source).unwrap_err();
+        assert_eq!(
+            (err.offset, err.message.as_str()),
+            (offset, message),
+            "{source}"
+        );
+    }
+}
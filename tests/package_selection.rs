@@ -0,0 +1,156 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use regex::Regex;
+use std::process::Command;
+
+#[test]
+fn workspace_root() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(list(&temp, "", &[])?, ["a", "b1", "b2"]);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn package_directory() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(list(&temp, "b1", &[])?, ["b1"]);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn package_subdirectory() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(list(&temp, "b1/src", &[])?, ["b1"]);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn package_glob() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(list(&temp, "", &["-p", "b*"])?, ["b1", "b2"]);
+    assert_eq!(
+        list(&temp, "b1", &["-p", "a", "--package", "b?"])?,
+        ["a", "b1", "b2"]
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn unknown_package() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list", "-p", "c*"]);
+    cmd.current_dir(temp.path());
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "package(s) `c*` not found in workspace",
+    ));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn workspace_and_exclude() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(
+        list(&temp, "b1", &["--workspace", "--exclude", "b?"])?,
+        ["a"]
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn manifest_path() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    assert_eq!(
+        list(&temp, "a", &["--manifest-path", "../b2/Cargo.toml"])?,
+        ["b2"]
+    );
+    assert_eq!(
+        list(&temp, "a", &["--manifest-path", "../Cargo.toml"])?,
+        ["a", "b1", "b2"]
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn cache_of_member() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = workspace_setup()?;
+    let run = || -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.args(["all-features", "check", "--cache"]);
+        cmd.env_remove("CARGO_TARGET_DIR");
+        cmd.current_dir(temp.path().join("a"));
+        let assert = cmd.assert().success();
+        Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+    };
+
+    assert_eq!(run()?.matches("Running").count(), 2);
+    assert_eq!(run()?.matches("Running").count(), 0);
+    // Build outputs left over in the member aren't sources
+    temp.child("a/target/debug/stale").write_str("stale")?;
+    temp.child("a/out/CACHEDIR.TAG")
+        .write_str("Signature: 8a477f597d28d172789f06886806bc55")?;
+    assert_eq!(run()?.matches("Running").count(), 0);
+    // The cargo configuration of the workspace root applies to the member too
+    temp.child(".cargo/config.toml")
+        .write_str("[build]\nrustflags = [\"--cfg\", \"changed\"]\n")?;
+    assert_eq!(run()?.matches("Running").count(), 2);
+    temp.close()?;
+    Ok(())
+}
+
+/*
+Setup a virtual workspace with the members a, b1 and b2, each with a single feature.
+*/
+fn workspace_setup() -> Result<assert_fs::TempDir, Box<dyn std::error::Error>> {
+    let temp = assert_fs::TempDir::new()?;
+    temp.child("Cargo.toml").write_str(
+        r#"
+        [workspace]
+        members = ["a", "b1", "b2"]
+        "#,
+    )?;
+    for name in ["a", "b1", "b2"] {
+        temp.child(format!("{name}/Cargo.toml"))
+            .write_str(&format!(
+                r#"
+            [package]
+            name = "{name}"
+            version = "0.1.0"
+
+            [features]
+            x = []
+            "#
+            ))?;
+        temp.child(format!("{name}/src/lib.rs")).touch()?;
+    }
+    Ok(temp)
+}
+
+/*
+Run `cargo all-features --list` with the given arguments in the given directory of the workspace
+and return the names of the listed crates.
+*/
+fn list(
+    temp: &assert_fs::TempDir,
+    dir: &str,
+    args: &[&str],
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list"]);
+    cmd.args(args);
+    cmd.current_dir(temp.path().join(dir));
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    let re = Regex::new(r"(?m)^crate=(\S+) features=\[\]$").unwrap();
+    let mut crates: Vec<String> = re.captures_iter(&stdout).map(|c| c[1].to_owned()).collect();
+    crates.sort();
+    Ok(crates)
+}
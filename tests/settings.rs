@@ -12,202 +12,1219 @@ fn simple() -> Result<(), Box<dyn std::error::Error>> {
         vec!["B"],
         vec!["C"],
         vec!["oDepB"],
-        vec!["A", "B"],
         vec!["A", "C"],
         vec!["A", "oDepB"],
         vec!["B", "C"],
         vec!["B", "oDepB"],
         vec!["C", "oDepB"],
-        vec!["A", "B", "C"],
-        vec!["A", "B", "oDepB"],
         vec!["A", "C", "oDepB"],
         vec!["B", "C", "oDepB"],
-        vec!["A", "B", "C", "oDepB"],
     ];
     test_settings("", valid_feature_sets, None)
 }
 
-#[test]
-fn skip_sets_1() -> Result<(), Box<dyn std::error::Error>> {
-    let settings = r#"
-        skip_feature_sets = [
-            ["C"],
-        ]
-    "#;
+#[test]
+fn skip_sets_1() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_feature_sets = [
+            ["C"],
+        ]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["oDepB"],
+        vec!["A", "oDepB"],
+        vec!["B", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn skip_sets_2() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_feature_sets = [
+            ["oDepB", "C"],
+        ]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["C"],
+        vec!["oDepB"],
+        vec!["A", "C"],
+        vec!["A", "oDepB"],
+        vec!["B", "C"],
+        vec!["B", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn skip_sets_3() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_feature_sets = [
+            ["oDepB", "B", "C"],
+        ]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["C"],
+        vec!["oDepB"],
+        vec!["A", "C"],
+        vec!["A", "oDepB"],
+        vec!["B", "C"],
+        vec!["B", "oDepB"],
+        vec!["C", "oDepB"],
+        vec!["A", "C", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn skip_opt_deps() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["C"],
+        vec!["A", "C"],
+        vec!["B", "C"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn allowlist() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        allowlist = ["A", "oDepB"]
+    "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"], vec!["A", "oDepB"]];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn denylist() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        denylist = ["C"]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["oDepB"],
+        vec!["A", "oDepB"],
+        vec!["B", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn extra_feats() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+        extra_features = ["oDepB"]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["C"],
+        vec!["oDepB"],
+        vec!["A", "C"],
+        vec!["A", "oDepB"],
+        vec!["B", "C"],
+        vec!["B", "oDepB"],
+        vec!["C", "oDepB"],
+        vec!["A", "C", "oDepB"],
+        vec!["B", "C", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn always_include() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        #skip_optional_dependencies = true
+        denylist = ["C"]
+        always_include_features = ["A"]
+    "#;
+    let valid_feature_sets = vec![
+        vec!["A"],
+        vec!["A", "B"],
+        vec!["A", "oDepB"],
+        vec!["A", "B", "oDepB"],
+    ];
+    test_settings(settings, valid_feature_sets, None)
+}
+
+#[test]
+fn skip_sets_with_always_include() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_feature_sets = [["A", "B"]]
+        always_include_features = ["A"]
+    "#;
+    test_settings(
+        settings,
+        vec![],
+        Some("Package testdummy has feature A in both `skip_feature_sets` and `always_include_features`"),
+    )
+}
+
+#[test]
+fn allowlist_with_skip_opt_deps() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+        allowlist = ["A", "B", "oDepB"]
+    "#;
+    test_settings(
+        settings,
+        vec![],
+        Some("Package testdummy has both `allowlist` and `skip_optional_dependencies` keys"),
+    )
+}
+
+#[test]
+fn parallel_jobs() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["C"],
+        vec!["A", "C"],
+        vec!["B", "C"],
+    ];
+    test_settings_with_args(settings, &["--parallel", "3"], valid_feature_sets, None)
+}
+
+#[cfg(unix)]
+#[test]
+fn cargo_flags_are_passed_down() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        allowlist = ["A"]
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that logs its arguments
+    let cargo = fake_cargo(&temp, "echo \"$*\" >> args.log")?;
+    let args_log = temp.child("args.log");
+    let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let _ = std::fs::remove_file(args_log.path());
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.arg("all-features");
+        cmd.args(args);
+        cmd.env("CARGO", &cargo);
+        cmd.current_dir(temp.path());
+        cmd.assert().success();
+        Ok(std::fs::read_to_string(args_log.path())?)
+    };
+
+    let log = run(&["test", "--jobs", "2"])?;
+    assert_eq!(
+        log.matches("test --jobs 2 --no-default-features").count(),
+        2
+    );
+    temp.child("src/main.rs").write_str("fn main() {}\n")?;
+    let log = run(&["check", "--keep-going"])?;
+    assert_eq!(
+        log.matches("check --keep-going --no-default-features")
+            .count(),
+        2
+    );
+    // Only the commands that know `--no-fail-fast` get it
+    let log = run(&["test", "--no-fail-fast"])?;
+    assert_eq!(
+        log.matches("test --no-fail-fast --no-default-features")
+            .count(),
+        2
+    );
+    let log = run(&["check", "--no-fail-fast"])?;
+    assert_eq!(log.matches("check --no-default-features").count(), 2);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn continue_on_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    temp.child("src/main.rs")
+        .write_str(r#"#[cfg(all(feature = "A", feature = "C"))] compile_error!("A and C");"#)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--continue-on-failure"]);
+    cmd.current_dir(temp.path());
+
+    let stdout = String::from_utf8(cmd.assert().failure().get_output().stdout.clone())?;
+    assert_eq!(get_tested_feature_sets_from_output(&stdout).len(), 6);
+    assert!(Regex::new(r"(?m)^.*FAIL.*testdummy +\[A,C\]$")?.is_match(&stdout));
+    assert!(Regex::new(r"(?m)^.*pass.*testdummy +\[B\]$")?.is_match(&stdout));
+    assert!(stdout.contains("4 passed; 2 failed"));
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn killed_by_signal() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that is killed when enabling feature C
+    let cargo = fake_cargo(&temp, "case \"$*\" in *C*) kill -KILL $$;; esac")?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--continue-on-failure"]);
+    cmd.env("CARGO", &cargo);
+    cmd.current_dir(temp.path());
+
+    let assert = cmd.assert().code(128 + 9);
+    let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert!(Regex::new(
+        r"(?m)^.*Killed.*crate=testdummy features=\[A,C\] \(killed by signal 9 \(SIGKILL\)\)$"
+    )?
+    .is_match(&stdout));
+    assert!(
+        Regex::new(r"(?m)^.*FAIL.*testdummy +\[C\]  \(killed by signal 9 \(SIGKILL\)\)$")?
+            .is_match(&stdout)
+    );
+    assert!(stdout.contains("3 passed; 3 failed"));
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn interrupted() -> Result<(), Box<dyn std::error::Error>> {
+    use std::{thread, time::Duration};
+
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that hangs when enabling feature C, until it is interrupted
+    let cargo = fake_cargo(
+        &temp,
+        "case \"$*\" in *C*) touch hanging; exec sleep 60;; esac",
+    )?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test"]);
+    cmd.env("CARGO", &cargo);
+    cmd.current_dir(temp.path());
+    cmd.stdout(std::process::Stdio::piped());
+    let child = cmd.spawn()?;
+
+    while !temp.child("hanging").exists() {
+        thread::sleep(Duration::from_millis(50));
+    }
+    Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()?;
+
+    let output = child.wait_with_output()?;
+    assert_eq!(output.status.code(), Some(130));
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(Regex::new(r"(?m)^.*interrupted.*testdummy +\[C\]$")?.is_match(&stdout));
+    assert!(Regex::new(r"(?m)^.*not started.*testdummy +\[A,C\]$")?.is_match(&stdout));
+    assert!(
+        Regex::new(r"(?m)^\d+ passed; 0 failed; 1 interrupted; \d+ not started$")?
+            .is_match(&stdout)
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn cargo_interrupted_before_handler() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that receives SIGINT when enabling feature C, as from a terminal, while
+    // cargo-all-features itself doesn't
+    let cargo = fake_cargo(&temp, "case \"$*\" in *C*) kill -INT $$;; esac")?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--continue-on-failure"]);
+    cmd.env("CARGO", &cargo);
+    cmd.current_dir(temp.path());
+
+    let assert = cmd.assert().code(130);
+    let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert!(Regex::new(r"(?m)^.*interrupted.*testdummy +\[C\]$")?.is_match(&stdout));
+    assert!(
+        Regex::new(r"(?m)^\d+ passed; 0 failed; 1 interrupted; \d+ not started$")?
+            .is_match(&stdout)
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn resume() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that fails when enabling feature C, until it is fixed
+    let cargo = fake_cargo(&temp, "case \"$*\" in *C*) exit 1;; esac")?;
+    let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.args(["all-features", "test"]);
+        cmd.args(args);
+        cmd.env("CARGO", &cargo);
+        cmd.env_remove("CARGO_TARGET_DIR");
+        cmd.current_dir(temp.path());
+        let output = cmd.output()?;
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 4);
+    temp.child("target/cargo-all-features/state.json")
+        .assert(predicate::str::contains("\"testdummy\""));
+
+    fake_cargo(&temp, "")?;
+    let stdout = run(&["--resume"])?;
+    assert!(stdout.contains("skipping 3 feature combinations that passed before"));
+    assert_eq!(stdout.matches("Running").count(), 3);
+
+    // Nothing is left to run
+    let stdout = run(&["--resume"])?;
+    assert!(stdout.contains("skipping 6 feature combinations that passed before"));
+    assert_eq!(stdout.matches("Running").count(), 0);
+
+    // Only the remaining combinations are listed
+    let stdout = run(&["--resume", "--list"])?;
+    assert!(stdout.is_empty());
+
+    // Different arguments are a different run
+    let stdout = run(&["--resume", "--release"])?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+
+    // Without `--resume` the run starts over, and is what a later `--resume` continues
+    run(&[])?;
+    let stdout = run(&["--resume"])?;
+    assert_eq!(stdout.matches("Running").count(), 0);
+
+    // Another workspace sharing the target directory has a state of its own
+    let other = dummy_crate_setup(settings)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--resume"]);
+    cmd.env("CARGO_TARGET_DIR", temp.path().join("target"));
+    cmd.current_dir(other.path());
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+    other.close()?;
+
+    // A state file written by an older version doesn't stop the run
+    temp.child("target/cargo-all-features/state.json").write_str(
+        r#"{ "passed": [{ "package": "testdummy", "features": [], "command": "test", "args": [] }], "failed": [] }"#,
+    )?;
+    // Listing doesn't need the state
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list", "test"]);
+    cmd.env_remove("CARGO_TARGET_DIR");
+    cmd.current_dir(temp.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("malformed").not());
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test"]);
+    cmd.env("CARGO", &cargo);
+    cmd.env_remove("CARGO_TARGET_DIR");
+    cmd.current_dir(temp.path());
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("state.json is malformed"));
+    temp.child("target/cargo-all-features/state.json")
+        .assert(predicate::str::contains("\"workspace\""));
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn concurrent_runs_keep_each_others_state() -> Result<(), Box<dyn std::error::Error>> {
+    use std::{thread, time::Duration};
+
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    temp.child("src/main.rs").write_str("fn main() {}\n")?;
+    // A cargo that waits for the other run to finish when testing feature C
+    let cargo = fake_cargo(
+        &temp,
+        "case \"$*\" in test*C*) touch waiting; while [ ! -e finished ]; do sleep 0.1; done;; esac",
+    )?;
+    let command = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.arg("all-features");
+        cmd.args(args);
+        cmd.env("CARGO", &cargo);
+        cmd.env_remove("CARGO_TARGET_DIR");
+        cmd.current_dir(temp.path());
+        Ok(cmd)
+    };
+
+    let mut test = command(&["test"])?;
+    test.stdout(std::process::Stdio::null());
+    let mut test = test.spawn()?;
+    while !temp.child("waiting").exists() {
+        thread::sleep(Duration::from_millis(50));
+    }
+    command(&["check"])?.assert().success();
+    temp.child("finished").touch()?;
+    assert!(test.wait()?.success());
+
+    // Neither run lost the combinations recorded by the other
+    for cargo_command in ["test", "check"] {
+        let assert = command(&[cargo_command, "--resume"])?.assert().success();
+        let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+        assert_eq!(stdout.matches("Running").count(), 0);
+    }
+    temp.close()?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn failed() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    // A cargo that fails when enabling the given features
+    let fail_with = |features: &str| -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+        fake_cargo(
+            &temp,
+            &format!("case \"$*\" in *{features}*) exit 1;; esac"),
+        )
+    };
+    let cargo = fail_with("C")?;
+    let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.args(["all-features", "test", "--continue-on-failure"]);
+        cmd.args(args);
+        cmd.env("CARGO", &cargo);
+        cmd.env_remove("CARGO_TARGET_DIR");
+        cmd.current_dir(temp.path());
+        let output = cmd.output()?;
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    let stdout = run(&[])?;
+    assert!(stdout.contains("3 passed; 3 failed"));
+
+    fail_with("B,C")?;
+    let stdout = run(&["--failed"])?;
+    assert!(stdout.contains("3 feature combinations that failed before"));
+    assert!(stdout.contains("2 passed; 1 failed"));
+
+    fail_with("nothing")?;
+    let stdout = run(&["--failed"])?;
+    assert!(stdout.contains("1 feature combination that failed before"));
+    assert!(stdout.contains("1 passed; 0 failed"));
+
+    let stdout = run(&["--failed"])?;
+    assert_eq!(stdout.matches("Running").count(), 0);
+    // The reruns are recorded as passing too
+    let stdout = run(&["--resume"])?;
+    assert!(stdout.contains("skipping 6 feature combinations that passed before"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn cache() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    let run = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.args(["all-features", "test", "--cache"]);
+        cmd.args(args);
+        cmd.env_remove("CARGO_TARGET_DIR");
+        cmd.current_dir(temp.path());
+        let assert = cmd.assert().success();
+        Ok(String::from_utf8(assert.get_output().stdout.clone())?)
+    };
+
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 0);
+    assert_eq!(stdout.matches("Cached").count(), 6);
+
+    // Different arguments
+    let stdout = run(&["--release"])?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+
+    // Changed sources
+    temp.child("src/main.rs").write_str("fn main() {}\n")?;
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 0);
+
+    // Different compiler flags
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--cache"]);
+    cmd.env_remove("CARGO_TARGET_DIR");
+    cmd.env("RUSTFLAGS", "--cfg changed");
+    cmd.current_dir(temp.path());
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+
+    // A malformed cache doesn't stop the run
+    temp.child("target/cargo-all-features/cache.json")
+        .write_str("{")?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--cache"]);
+    cmd.env_remove("CARGO_TARGET_DIR");
+    cmd.current_dir(temp.path());
+    let assert = cmd
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("cache.json is malformed"));
+    let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+    assert_eq!(stdout.matches("Running").count(), 6);
+    let stdout = run(&[])?;
+    assert_eq!(stdout.matches("Running").count(), 0);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn list() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        denylist = ["C"]
+        always_include_features = ["A"]
+    "#;
+    let valid_feature_sets = vec![
+        vec!["A"],
+        vec!["A", "B"],
+        vec!["A", "oDepB"],
+        vec!["A", "B", "oDepB"],
+    ];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn json_messages() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--message-format", "json"]);
+    cmd.current_dir(temp.path());
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    let messages = stdout
+        .lines()
+        .map(json::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let reasons: Vec<_> = messages.iter().map(|m| m["reason"].as_str()).collect();
+    assert_eq!(reasons.first(), Some(&Some("feature-matrix")));
+    assert_eq!(reasons.last(), Some(&Some("summary")));
+    assert_eq!(messages[0]["combinations"].len(), 6);
+    let finished: Vec<_> = messages
+        .iter()
+        .filter(|m| m["reason"] == "combination-finished")
+        .collect();
+    assert_eq!(finished.len(), 6);
+    assert!(finished
+        .iter()
+        .all(|m| m["success"] == true && m["package"] == "testdummy"));
+    let summary = messages.last().unwrap();
+    assert_eq!(summary["passed"], 6);
+    assert_eq!(summary["failed"], 0);
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn junit_report() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        skip_optional_dependencies = true
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    temp.child("src/main.rs")
+        .write_str(r#"#[cfg(all(feature = "A", feature = "C"))] compile_error!("A and C");"#)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "test", "--junit", "report.xml"]);
+    cmd.current_dir(temp.path());
+    cmd.assert().failure();
+
+    let report = std::fs::read_to_string(temp.child("report.xml").path())?;
+    assert!(report.contains(r#"<testsuite name="testdummy" tests="6" failures="1" skipped="1""#));
+    assert!(report.contains(r#"<testcase name="features=[B]" classname="testdummy""#));
+    assert!(
+        Regex::new(r#"(?s)<testcase name="features=\[A,C\]".*<failure.*A and C.*</failure>"#)?
+            .is_match(&report)
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn minimize() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = dummy_crate_setup("")?;
+    temp.child("src/main.rs")
+        .write_str(r#"#[cfg(all(feature = "B", feature = "C"))] compile_error!("B and C");"#)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    // Only run the last combination, [oDepB,B,C]
+    cmd.args(["all-features", "test", "--minimize"]);
+    cmd.args([
+        "--chunk-granularity",
+        "feature",
+        "--n-chunks",
+        "12",
+        "--chunk",
+        "12",
+    ]);
+    cmd.current_dir(temp.path());
+
+    let stdout = String::from_utf8(cmd.assert().failure().get_output().stdout.clone())?;
+    assert!(Regex::new(
+        r"(?m)^.*Minimized.*crate=testdummy features=\[oDepB,B,C\] to features=\[B,C\]$"
+    )?
+    .is_match(&stdout));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn implied_features_are_deduplicated() -> Result<(), Box<dyn std::error::Error>> {
+    // B = ["A"], so [A,B] builds the same as [B]
+    let settings = r#"
+        allowlist = ["A", "B"]
+    "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["B"]];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn mutually_exclusive() -> Result<(), Box<dyn std::error::Error>> {
+    // B = ["A"], so B is exclusive with C and oDepB as well
+    let settings = r#"
+        mutually_exclusive_features = [["A", "C", "oDepB"]]
+    "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["B"], vec!["C"], vec!["oDepB"]];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn mutually_exclusive_with_always_include() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        mutually_exclusive_features = [["A", "C", "oDepB"]]
+        always_include_features = ["A", "C"]
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has mutually exclusive features A and C in `always_include_features`"),
+    )
+}
+
+#[test]
+fn require_one_of() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        require_one_of = [["C", "oDepB"]]
+    "#;
+    let valid_feature_sets = vec![
+        vec!["C"],
+        vec!["oDepB"],
+        vec!["A", "C"],
+        vec!["B", "C"],
+        vec!["A", "oDepB"],
+        vec!["B", "oDepB"],
+    ];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn feature_requires() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        feature_requires = { C = ["oDepB"] }
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["oDepB"],
+        vec!["A", "oDepB"],
+        vec!["B", "oDepB"],
+        vec!["C", "oDepB"],
+        vec!["A", "C", "oDepB"],
+        vec!["B", "C", "oDepB"],
+    ];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn constraints() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        constraints = ["!(A && C)", "C -> oDepB"]
+    "#;
+    let valid_feature_sets = vec![
+        vec![],
+        vec!["A"],
+        vec!["B"],
+        vec!["oDepB"],
+        vec!["A", "oDepB"],
+        vec!["B", "oDepB"],
+        vec!["C", "oDepB"],
+    ];
+    test_list_settings(settings, &[], valid_feature_sets, None)
+}
+
+#[test]
+fn invalid_constraint() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        constraints = ["A &&"]
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("expected a feature or `(`, found the end at offset 4"),
+    )
+}
+
+#[test]
+fn pairwise() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        strategy = "pairwise"
+        skip_feature_sets = [["A", "C"]]
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list"]);
+    cmd.current_dir(temp.path());
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+    let feature_sets = get_listed_feature_sets_from_output(&stdout);
+
+    // Fewer than the 12 sets of the powerset
+    assert!(feature_sets.len() < 12);
+    assert!(!feature_sets
+        .iter()
+        .any(|set| set.contains(&"A") && set.contains(&"C")));
+    // Every other on/off state of every pair of features is covered
+    let features = ["A", "B", "C", "oDepB"];
+    for (i, a) in features.iter().enumerate() {
+        for b in &features[i + 1..] {
+            for (a_on, b_on) in [(false, false), (false, true), (true, false), (true, true)] {
+                if (*a, *b, a_on, b_on) == ("A", "C", true, true) {
+                    continue;
+                }
+                assert!(
+                    feature_sets
+                        .iter()
+                        .any(|set| set.contains(a) == a_on && set.contains(b) == b_on),
+                    "{a}={a_on} {b}={b_on} is not covered by {feature_sets:?}"
+                );
+            }
+        }
+    }
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn unknown_strategy() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        strategy = "pairs"
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has unknown `strategy`"),
+    )
+}
+
+#[test]
+fn workspace_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = dummy_workspace_setup(Some(r#"denylist = ["C"]"#), "")?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list", "--print-config"]);
+    cmd.current_dir(temp.path());
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap();
+    let config = json::parse(&stdout[..stdout.find("\ncrate=").unwrap()])?;
+    assert_eq!(config["testdummy"]["denylist"], json::array!["C"]);
+    // Path dependencies inside the workspace directory are members too
+    assert_eq!(config["fixDepA"]["denylist"], json::array!["C"]);
+
     let valid_feature_sets = vec![
         vec![],
         vec!["A"],
         vec!["B"],
         vec!["oDepB"],
-        vec!["A", "B"],
         vec!["A", "oDepB"],
         vec!["B", "oDepB"],
-        vec!["A", "B", "oDepB"],
     ];
-    test_settings(settings, valid_feature_sets, None)
+    assert_eq!(
+        valid_feature_sets.normalize(),
+        get_listed_feature_sets_from_output(&stdout).normalize()
+    );
+    temp.close()?;
+    Ok(())
 }
 
 #[test]
-fn skip_sets_2() -> Result<(), Box<dyn std::error::Error>> {
+fn package_settings_override_workspace_defaults() -> Result<(), Box<dyn std::error::Error>> {
+    let temp = dummy_workspace_setup(Some(r#"denylist = ["C"]"#), r#"allowlist = ["A", "oDepB"]"#)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list"]);
+    cmd.current_dir(temp.path());
+
+    let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap();
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"], vec!["A", "oDepB"]];
+    assert_eq!(
+        valid_feature_sets.normalize(),
+        get_listed_feature_sets_from_output(&stdout).normalize()
+    );
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn cli_denylist_replaces_denylist() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        skip_feature_sets = [
-            ["oDepB", "C"],
-        ]
+        denylist = ["A"]
     "#;
     let valid_feature_sets = vec![
         vec![],
         vec!["A"],
         vec!["B"],
-        vec!["C"],
         vec!["oDepB"],
-        vec!["A", "B"],
-        vec!["A", "C"],
         vec!["A", "oDepB"],
-        vec!["B", "C"],
         vec!["B", "oDepB"],
-        vec!["A", "B", "C"],
-        vec!["A", "B", "oDepB"],
     ];
-    test_settings(settings, valid_feature_sets, None)
+    test_list_settings(settings, &["--denylist", "C"], valid_feature_sets, None)
 }
 
 #[test]
-fn skip_sets_3() -> Result<(), Box<dyn std::error::Error>> {
+fn cli_allowlist_replaces_allowlist() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        skip_feature_sets = [
-            ["oDepB", "B", "C"],
-        ]
+        allowlist = ["A", "C"]
     "#;
-    let valid_feature_sets = vec![
-        vec![],
-        vec!["A"],
-        vec!["B"],
-        vec!["C"],
-        vec!["oDepB"],
-        vec!["A", "B"],
-        vec!["A", "C"],
-        vec!["A", "oDepB"],
-        vec!["B", "C"],
-        vec!["B", "oDepB"],
-        vec!["C", "oDepB"],
-        vec!["A", "B", "C"],
-        vec!["A", "B", "oDepB"],
-        vec!["A", "C", "oDepB"],
-    ];
-    test_settings(settings, valid_feature_sets, None)
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"], vec!["A", "oDepB"]];
+    test_list_settings(
+        settings,
+        &["--allowlist", "A,oDepB"],
+        valid_feature_sets,
+        None,
+    )
 }
 
 #[test]
-fn skip_opt_deps() -> Result<(), Box<dyn std::error::Error>> {
+fn cli_allowlist_overrides_conflicting_settings() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
+        denylist = ["C"]
         skip_optional_dependencies = true
     "#;
-    let valid_feature_sets = vec![
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"], vec!["A", "oDepB"]];
+    test_list_settings(
+        settings,
+        &["--allowlist", "A,oDepB"],
+        valid_feature_sets,
+        None,
+    )
+}
+
+#[test]
+fn cli_allowlist_conflicting_with_cli_options() -> Result<(), Box<dyn std::error::Error>> {
+    test_list_settings(
+        "",
+        &["--allowlist", "A,oDepB", "--denylist", "C"],
         vec![],
-        vec!["A"],
-        vec!["B"],
-        vec!["C"],
-        vec!["A", "B"],
-        vec!["A", "C"],
-        vec!["B", "C"],
-        vec!["A", "B", "C"],
-    ];
-    test_settings(settings, valid_feature_sets, None)
+        Some("Package testdummy has both `allowlist` and `denylist` keys"),
+    )
 }
 
 #[test]
-fn allowlist() -> Result<(), Box<dyn std::error::Error>> {
+fn cli_max_combination_size_keeps_allowlist() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
         allowlist = ["A", "oDepB"]
     "#;
-    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"], vec!["A", "oDepB"]];
-    test_settings(settings, valid_feature_sets, None)
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"]];
+    test_list_settings(
+        settings,
+        &["--max-combination-size", "1"],
+        valid_feature_sets,
+        None,
+    )
 }
 
 #[test]
-fn denylist() -> Result<(), Box<dyn std::error::Error>> {
+fn cli_skip_feature_sets_extend_settings() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        denylist = ["C"]
+        skip_feature_sets = [["C"]]
     "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["B"], vec!["oDepB"]];
+    test_list_settings(
+        settings,
+        &[
+            "--skip-feature-set",
+            "A,oDepB",
+            "--skip-feature-set=B,oDepB",
+        ],
+        valid_feature_sets,
+        None,
+    )
+}
+
+#[test]
+fn cli_max_combination_size_and_always_include() -> Result<(), Box<dyn std::error::Error>> {
     let valid_feature_sets = vec![
-        vec![],
         vec!["A"],
-        vec!["B"],
-        vec!["oDepB"],
-        vec!["A", "B"],
         vec!["A", "oDepB"],
-        vec!["B", "oDepB"],
-        vec!["A", "B", "oDepB"],
+        vec!["A", "B"],
+        vec!["A", "C"],
     ];
+    test_list_settings(
+        "",
+        &["--always-include", "A", "--max-combination-size", "1"],
+        valid_feature_sets,
+        None,
+    )
+}
+
+#[test]
+fn command_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        denylist = ["C"]
+        test = { max_combination_size = 1 }
+        check = { denylist = ["A", "B"] }
+    "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["B"], vec!["oDepB"]];
     test_settings(settings, valid_feature_sets, None)
 }
 
 #[test]
-fn extra_feats() -> Result<(), Box<dyn std::error::Error>> {
+fn command_settings_are_listed_for_their_command() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        skip_optional_dependencies = true
-        extra_features = ["oDepB"]
+        denylist = ["C"]
+        test = { max_combination_size = 1 }
+        check = { denylist = ["A"] }
     "#;
     let valid_feature_sets = vec![
         vec![],
-        vec!["A"],
         vec!["B"],
         vec!["C"],
         vec!["oDepB"],
-        vec!["A", "B"],
-        vec!["A", "C"],
-        vec!["A", "oDepB"],
         vec!["B", "C"],
         vec!["B", "oDepB"],
         vec!["C", "oDepB"],
-        vec!["A", "B", "C"],
-        vec!["A", "B", "oDepB"],
-        vec!["A", "C", "oDepB"],
         vec!["B", "C", "oDepB"],
-        vec!["A", "B", "C", "oDepB"],
     ];
-    test_settings(settings, valid_feature_sets, None)
+    test_list_settings(settings, &["check"], valid_feature_sets, None)
 }
 
 #[test]
-fn always_include() -> Result<(), Box<dyn std::error::Error>> {
+fn command_settings_keep_allowlist() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        allowlist = ["A", "oDepB"]
+        test = { max_combination_size = 1 }
+    "#;
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["oDepB"]];
+    test_list_settings(settings, &["test"], valid_feature_sets, None)
+}
+
+#[test]
+fn command_settings_replace_conflicting_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        allowlist = ["A", "oDepB"]
+        test = { denylist = ["oDepB"] }
+    "#;
+    test_list_settings(
+        settings,
+        &["test"],
+        vec![
+            vec![],
+            vec!["A"],
+            vec!["B"],
+            vec!["C"],
+            vec!["A", "C"],
+            vec!["B", "C"],
+        ],
+        None,
+    )?;
+    test_list_settings(
+        r#"
+            denylist = ["B"]
+            test = { allowlist = ["A"] }
+        "#,
+        &["test"],
+        vec![vec![], vec!["A"]],
+        None,
+    )
+}
+
+#[test]
+fn command_settings_conflicting_with_each_other() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        test = { allowlist = ["A"], denylist = ["oDepB"] }
+    "#;
+    test_list_settings(
+        settings,
+        &["test"],
+        vec![],
+        Some("Package testdummy has both `allowlist` and `denylist` keys"),
+    )
+}
+
+#[test]
+fn profile() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        #skip_optional_dependencies = true
         denylist = ["C"]
-        always_include_features = ["A"]
+        check = { denylist = ["A"] }
+        profiles.quick = { max_combination_size = 1 }
+        profiles.exhaustive = { denylist = [] }
     "#;
-    let valid_feature_sets = vec![
-        vec!["A"],
-        vec!["A", "B"],
-        vec!["A", "oDepB"],
-        vec!["A", "B", "oDepB"],
-    ];
-    test_settings(settings, valid_feature_sets, None)
+    let valid_feature_sets = vec![vec![], vec!["B"], vec!["C"], vec!["oDepB"]];
+    test_list_settings(
+        settings,
+        &["--profile", "quick", "check"],
+        valid_feature_sets,
+        None,
+    )
 }
 
 #[test]
-fn skip_sets_with_always_include() -> Result<(), Box<dyn std::error::Error>> {
+fn profile_replaces_conflicting_settings() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        skip_feature_sets = [["A", "B"]]
-        always_include_features = ["A"]
+        denylist = ["C"]
+        profiles.narrow = { allowlist = ["A", "C"] }
     "#;
-    test_settings(
+    let valid_feature_sets = vec![vec![], vec!["A"], vec!["C"], vec!["A", "C"]];
+    test_list_settings(
         settings,
+        &["--profile", "narrow", "check"],
+        valid_feature_sets,
+        None,
+    )
+}
+
+#[test]
+fn unknown_profile_is_passed_to_cargo() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        profiles.quick = { max_combination_size = 1 }
+    "#;
+    test_settings_with_args(
+        settings,
+        &["--profile", "nonexistent"],
         vec![],
-        Some("Package testdummy has feature A in both `skip_feature_sets` and `always_include_features`"),
+        Some("profile `nonexistent` is not defined"),
     )
 }
 
 #[test]
-fn allowlist_with_skip_opt_deps() -> Result<(), Box<dyn std::error::Error>> {
+fn unknown_key() -> Result<(), Box<dyn std::error::Error>> {
     let settings = r#"
-        skip_optional_dependencies = true
-        allowlist = ["A", "B", "oDepB"]
+        skip_feature_set = [["A", "C"]]
     "#;
-    test_settings(
+    test_list_settings(
         settings,
+        &[],
         vec![],
-        Some("Package testdummy has both `allowlist` and `skip_optional_dependencies` keys"),
+        Some("Package testdummy has unknown key `skip_feature_set` in `[package.metadata.cargo-all-features]`, did you mean `skip_feature_sets`?"),
+    )
+}
+
+#[test]
+fn misspelled_command_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        tset = { max_combination_size = 1 }
+        nextest = { max_combination_size = 1 }
+    "#;
+    let temp = dummy_crate_setup(settings)?;
+    let run = |command: &str| -> Result<String, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("cargo-all-features")?;
+        cmd.args(["all-features", "--list", command]);
+        cmd.current_dir(temp.path());
+        let assert = cmd.assert().success();
+        Ok(String::from_utf8(assert.get_output().stderr.clone())?)
+    };
+
+    let stderr = run("test")?;
+    assert!(stderr.contains("Package testdummy has options for unknown cargo command `tset` in `[package.metadata.cargo-all-features.tset]`, did you mean `test`?"));
+    assert!(stderr.contains("unknown cargo command `nextest`"));
+    // The options for the command being run are expected
+    let stderr = run("nextest")?;
+    assert!(!stderr.contains("unknown cargo command `nextest`"));
+    temp.close()?;
+    Ok(())
+}
+
+#[test]
+fn unknown_key_in_command_settings() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        test = { deny_list = ["C"] }
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has unknown key `deny_list` in `[package.metadata.cargo-all-features.test]`, did you mean `denylist`?"),
+    )
+}
+
+#[test]
+fn wrong_type() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        denylist = "C"
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has `denylist` of the wrong type in `[package.metadata.cargo-all-features]`, expected an array of strings"),
+    )
+}
+
+#[test]
+fn unknown_feature() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        denylist = ["C", "D"]
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has unknown feature `D` in `denylist` of `[package.metadata.cargo-all-features]`"),
+    )
+}
+
+#[test]
+fn unknown_feature_in_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = r#"
+        constraints = ["C -> oDepB", "dep:optDepC -> oDepB?/default"]
+        profiles.quick = { skip_feature_sets = [["A", "oDepC"]] }
+    "#;
+    test_list_settings(
+        settings,
+        &[],
+        vec![],
+        Some("Package testdummy has unknown feature `oDepC` in `skip_feature_sets` of `[package.metadata.cargo-all-features.profiles.quick]`"),
     )
 }
 
@@ -285,10 +1302,41 @@ fn get_tested_feature_sets_from_output(stdout: &str) -> Vec<Vec<&str>> {
     ans
 }
 
+/*
+`cargo all-features --list` prints one line per feature set without running cargo, e.g.
+crate=testdummy features=[A,B].
+*/
+fn get_listed_feature_sets_from_output(stdout: &str) -> Vec<Vec<&str>> {
+    let re = Regex::new(r"(?m)^crate=testdummy features=\[(.*)\]$").unwrap();
+
+    let mut ans = vec![];
+    for (_, [comma_sep_features]) in re.captures_iter(stdout).map(|c| c.extract()) {
+        ans.push(comma_sep_features.split(',').collect());
+    }
+    ans
+}
+
 /*
 Setup a compilable dummy crate with no implementation and return the path to this crate layout.
 */
 fn dummy_crate_setup(settings: &str) -> Result<assert_fs::TempDir, Box<dyn std::error::Error>> {
+    dummy_workspace_setup(None, settings)
+}
+
+/*
+Like dummy_crate_setup, but if workspace settings are given, the dummy crate is also the root of a
+workspace with these settings.
+*/
+fn dummy_workspace_setup(
+    workspace_settings: Option<&str>,
+    settings: &str,
+) -> Result<assert_fs::TempDir, Box<dyn std::error::Error>> {
+    let workspace = match workspace_settings {
+        Some(workspace_settings) => {
+            format!("[workspace.metadata.cargo-all-features]\n{workspace_settings}")
+        }
+        None => String::new(),
+    };
     let temp = assert_fs::TempDir::new()?;
     cargo_dep_setup("fixDepA", temp.path())?;
     cargo_dep_setup("optDepB", temp.path())?;
@@ -312,12 +1360,31 @@ fn dummy_crate_setup(settings: &str) -> Result<assert_fs::TempDir, Box<dyn std::
 
         [package.metadata.cargo-all-features]
         {settings}
+
+        {workspace}
         "#
     ))?;
     temp.child("src/main.rs").touch()?;
     Ok(temp)
 }
 
+/*
+Writes a cargo.sh to `temp` that runs the shell `script` and then the real cargo, and returns its
+path to be set as the CARGO environment variable.
+*/
+#[cfg(unix)]
+fn fake_cargo(
+    temp: &assert_fs::TempDir,
+    script: &str,
+) -> Result<std::path::PathBuf, Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cargo = temp.child("cargo.sh");
+    cargo.write_str(&format!("#!/bin/sh\n{script}\nexec cargo \"$@\"\n"))?;
+    std::fs::set_permissions(cargo.path(), std::fs::Permissions::from_mode(0o755))?;
+    Ok(cargo.path().to_owned())
+}
+
 /*
 Test the given settings for cargo-all-features.
 If an error message is provided, expect cargo test-all-features to fail with this message.
@@ -327,10 +1394,23 @@ fn test_settings(
     settings: &str,
     valid_feature_sets: Vec<Vec<&str>>,
     expected_error: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    test_settings_with_args(settings, &[], valid_feature_sets, expected_error)
+}
+
+/*
+Same as test_settings, but passes the given extra arguments to cargo-all-features.
+*/
+fn test_settings_with_args(
+    settings: &str,
+    args: &[&str],
+    valid_feature_sets: Vec<Vec<&str>>,
+    expected_error: Option<&str>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let temp = dummy_crate_setup(settings)?;
     let mut cmd = Command::cargo_bin("cargo-all-features")?;
     cmd.args(["all-features", "test"]);
+    cmd.args(args);
     cmd.current_dir(temp.path());
 
     // add flags for producing also a coverage report, see ci/test_and_coverage.bash
@@ -359,3 +1439,31 @@ fn test_settings(
     temp.close()?;
     Ok(())
 }
+
+/*
+Like test_settings, but only lists the feature sets with `--list` instead of running cargo test for each of them.
+*/
+fn test_list_settings(
+    settings: &str,
+    args: &[&str],
+    valid_feature_sets: Vec<Vec<&str>>,
+    expected_error: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let temp = dummy_crate_setup(settings)?;
+    let mut cmd = Command::cargo_bin("cargo-all-features")?;
+    cmd.args(["all-features", "--list"]);
+    cmd.args(args);
+    cmd.current_dir(temp.path());
+
+    if let Some(err_msg) = expected_error {
+        cmd.assert()
+            .failure()
+            .stderr(predicate::str::contains(err_msg));
+    } else {
+        let stdout = String::from_utf8(cmd.assert().success().get_output().stdout.clone()).unwrap();
+        let listed_feat_sets = get_listed_feature_sets_from_output(&stdout);
+        assert_eq!(valid_feature_sets.normalize(), listed_feat_sets.normalize());
+    }
+    temp.close()?;
+    Ok(())
+}